@@ -2,6 +2,7 @@
 
 use std::error;
 use std::fmt;
+use std::io;
 
 /// The error type for everything that can go wrong in FASTA parsing.
 #[derive(Debug)]
@@ -14,12 +15,57 @@ pub struct ParseError<'a> {
 pub enum ErrorKind {
     /// Index points to a line that is not a description line.
     IndexNotAtDescription,
+    /// No description line was found before the end of the file.
+    NoDescription,
+    /// The requested id is not in the index.
+    IdNotFound,
+    /// The same id was found for more than one entry.
+    DuplicateId,
+    /// A sequence contains a character that is not valid in its alphabet.
+    InvalidCharacter,
+    /// The id extracted from a description line is empty or whitespace.
+    EmptyId,
+    /// Entries are not in the required order.
+    NotSorted,
+    /// An index file is malformed.
+    InvalidIndex,
+    /// An id pattern does not match a description line.
+    NoIdMatch,
+    /// A file changed while it was being processed.
+    FileChanged,
+    /// An offset, e.g. from a stale index, points at or past the end of the file.
+    OffsetPastEof,
+    /// A sequence range is empty, reversed or exceeds the sequence.
+    InvalidRange,
+    /// The sequence lines of an entry are not wrapped to a uniform length.
+    IrregularLineLengths,
+    /// An index was used on a compressed file, which can't be read from an offset.
+    SeekOnCompressed,
+    /// A description line has fewer fields than the requested id index.
+    IdIndexOutOfRange,
+    /// A nucleotide sequence ends with an incomplete codon.
+    IncompleteCodon,
 }
 
 impl ErrorKind {
     pub(crate) fn as_str(self) -> &'static str {
         match self {
             ErrorKind::IndexNotAtDescription => "Index points to a non-description line.",
+            ErrorKind::NoDescription => "No description line found.",
+            ErrorKind::IdNotFound => "Id not found in index.",
+            ErrorKind::DuplicateId => "Multiple entries found for id.",
+            ErrorKind::InvalidCharacter => "Invalid character in sequence.",
+            ErrorKind::EmptyId => "Empty id in description line.",
+            ErrorKind::NotSorted => "Entries are not sorted.",
+            ErrorKind::InvalidIndex => "Malformed index file.",
+            ErrorKind::NoIdMatch => "Id pattern does not match description line.",
+            ErrorKind::FileChanged => "File changed during processing.",
+            ErrorKind::OffsetPastEof => "Offset points past the end of the file.",
+            ErrorKind::InvalidRange => "Invalid sequence range.",
+            ErrorKind::IrregularLineLengths => "Irregular sequence line lengths.",
+            ErrorKind::SeekOnCompressed => "Cannot seek in a compressed file.",
+            ErrorKind::IdIndexOutOfRange => "Id index out of range.",
+            ErrorKind::IncompleteCodon => "Incomplete codon.",
         }
     }
 }
@@ -41,3 +87,55 @@ impl<'a> error::Error for ParseError<'a> {
         self.kind.as_str()
     }
 }
+
+/// The error type for fallible operations on FASTA files.
+#[derive(Debug)]
+pub enum FastaError {
+    /// An I/O error from the underlying file.
+    Io(io::Error),
+    /// The file content could not be parsed as FASTA.
+    Parse { kind: ErrorKind, message: String },
+}
+
+impl FastaError {
+    pub fn parse(kind: ErrorKind, message: impl Into<String>) -> FastaError {
+        FastaError::Parse {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FastaError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FastaError::Io(e) => write!(fmt, "{}", e),
+            FastaError::Parse { message, .. } => write!(fmt, "{}", message),
+        }
+    }
+}
+
+impl error::Error for FastaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FastaError::Io(e) => Some(e),
+            FastaError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for FastaError {
+    fn from(e: io::Error) -> Self {
+        FastaError::Io(e)
+    }
+}
+
+/// Parse errors become `InvalidData` I/O errors, for functions that return `io::Result`.
+impl From<FastaError> for io::Error {
+    fn from(e: FastaError) -> Self {
+        match e {
+            FastaError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
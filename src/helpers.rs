@@ -1,44 +1,644 @@
 //! Helper functions that perform operations used across the whole crate.
 
+use crate::errors::{ErrorKind, FastaError};
 use flate2::bufread::MultiGzDecoder;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, Chain, Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::Path;
 
-pub fn seq_id_from_description<'a>(line: &'a str, separator: &'a str, id_index: usize) -> &'a str {
-    if line.contains(separator) {
-        let fields = line.split(separator).collect::<Vec<&str>>();
-        if id_index == 0 {
-            &fields[id_index][1..]
-        } else {
-            fields[id_index]
+/// Extracts the id from a description line, with or without the leading `>`.
+///
+/// Whitespace around the description and around the extracted id is ignored,
+/// so `> Q2HZH0` and `>Q2HZH0 ` both yield `Q2HZH0`. If the description does
+/// not contain `separator`, or has no field `id_index`, the whole description
+/// is the id.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::seq_id_from_description;
+///
+/// assert_eq!(seq_id_from_description(">sp|Q2HZH0|IL1B", "|", 1), "Q2HZH0");
+/// assert_eq!(seq_id_from_description(">sp|Q2HZH0", "|", 3), "sp|Q2HZH0");
+/// ```
+pub fn seq_id_from_description<'a>(line: &'a str, separator: &str, id_index: usize) -> &'a str {
+    &line[seq_id_range(line, separator, id_index)]
+}
+
+// The byte range of the id in a description line, as returned by `seq_id_from_description`.
+pub(crate) fn seq_id_range(line: &str, separator: &str, id_index: usize) -> Range<usize> {
+    id_field_range(line, separator, id_index).unwrap_or_else(|| description_range(line))
+}
+
+// The id field of a description line, or `None` if there are too few fields.
+fn id_field<'a>(line: &'a str, separator: &str, id_index: usize) -> Option<&'a str> {
+    id_field_range(line, separator, id_index).map(|range| &line[range])
+}
+
+fn id_field_range(line: &str, separator: &str, id_index: usize) -> Option<Range<usize>> {
+    let description = description_range(line);
+    let trimmed = &line[description.clone()];
+    if !trimmed.contains(separator) {
+        return Some(description);
+    }
+    let mut field_start = description.start;
+    for (i, field) in trimmed.split(separator).enumerate() {
+        if i == id_index {
+            let start = field_start + field.len() - field.trim_start().len();
+            return Some(start..start + field.trim().len());
         }
+        field_start += field.len() + separator.len();
+    }
+    None
+}
+
+// The byte range of a description line without `>` and surrounding whitespace.
+fn description_range(line: &str) -> Range<usize> {
+    let description = line.strip_prefix('>').unwrap_or(line);
+    let start = line.len() - description.trim_start().len();
+    start..start + description.trim().len()
+}
+
+/// Parses NCBI-style database cross-references from a description line.
+///
+/// The first whitespace-delimited field, without a leading `>`, is split at
+/// `|` into alternating `db|accession` pairs. A trailing field without a
+/// partner, like the entry name in UniProt headers, is ignored. Descriptions
+/// without `|` in their first field yield no cross-references.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::parse_xrefs;
+///
+/// let xrefs = parse_xrefs(">gi|12345|ref|NP_001.1| some protein");
+/// assert_eq!(
+///     xrefs,
+///     vec![
+///         ("gi".to_string(), "12345".to_string()),
+///         ("ref".to_string(), "NP_001.1".to_string())
+///     ]
+/// );
+/// ```
+pub fn parse_xrefs(description: &str) -> Vec<(String, String)> {
+    let description = description.strip_prefix('>').unwrap_or(description);
+    let first = description.split_whitespace().next().unwrap_or("");
+    if !first.contains('|') {
+        return Vec::new();
+    }
+    let fields = first.split('|').collect::<Vec<_>>();
+    fields
+        .chunks_exact(2)
+        .filter(|pair| !pair[0].is_empty())
+        .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+        .collect()
+}
+
+/// Parses `TAG:value` fields, like annotation tracks, from a description line.
+///
+/// The description, without a leading `>`, is split at `tag_sep` into fields,
+/// and each field is split at its first `:` into tag and value. Fields
+/// without `:`, or whose tag is empty or contains whitespace, are ignored,
+/// so a description without tagged fields yields an empty map.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::parse_tagged_fields;
+///
+/// let fields = parse_tagged_fields(">1abc_A|SS:HHHEEECC|ACC:0.9", '|');
+/// assert_eq!(fields["SS"], "HHHEEECC");
+/// assert_eq!(fields["ACC"], "0.9");
+/// ```
+pub fn parse_tagged_fields(description: &str, tag_sep: char) -> HashMap<String, String> {
+    let description = description.strip_prefix('>').unwrap_or(description);
+    description
+        .split(tag_sep)
+        .filter_map(|field| field.trim().split_once(':'))
+        .filter(|(tag, _)| !tag.is_empty() && !tag.contains(char::is_whitespace))
+        .map(|(tag, value)| (tag.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Like `seq_id_from_description`, but fails on empty or whitespace-only ids
+/// and on descriptions without field `id_index`, which usually means that
+/// `separator` or `id_index` do not match the file.
+pub(crate) fn checked_seq_id<'a>(
+    line: &'a str,
+    separator: &str,
+    id_index: usize,
+) -> Result<&'a str, FastaError> {
+    let id = id_field(line, separator, id_index).ok_or_else(|| {
+        FastaError::parse(
+            ErrorKind::IdIndexOutOfRange,
+            format!(
+                "No field {} when splitting at {:?} in description line: {:?}",
+                id_index, separator, line
+            ),
+        )
+    })?;
+    if id.trim().is_empty() {
+        return Err(empty_id_error(line));
+    }
+    Ok(id)
+}
+
+pub(crate) fn offset_past_eof_error(offset: u64) -> FastaError {
+    FastaError::parse(
+        ErrorKind::OffsetPastEof,
+        format!(
+            "Nothing to read at offset {}; is the index outdated?",
+            offset
+        ),
+    )
+}
+
+pub(crate) fn seek_on_compressed_error(path: &Path) -> FastaError {
+    FastaError::parse(
+        ErrorKind::SeekOnCompressed,
+        format!(
+            "Tried to use index on non seekable compressed file: {:?}",
+            path
+        ),
+    )
+}
+
+pub(crate) fn empty_id_error(line: &str) -> FastaError {
+    FastaError::parse(
+        ErrorKind::EmptyId,
+        format!("Empty id in description line: {:?}", line),
+    )
+}
+
+/// Splits a description line into the id and the free-text rest.
+///
+/// The id is parsed as in `seq_id_from_description`. The rest is everything
+/// after the first whitespace following the id field, without leading
+/// whitespace, so further fields attached to the id, like the entry name in
+/// UniProt descriptions, are in neither. Descriptions without free text
+/// yield an empty rest.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::split_id_and_rest;
+///
+/// let (id, rest) = split_id_and_rest(">sp|Q2HZH0|IL1B_PUSHI Interleukin-1 beta", "|", 1);
+/// assert_eq!(id, "Q2HZH0");
+/// assert_eq!(rest, "Interleukin-1 beta");
+/// ```
+pub fn split_id_and_rest<'a>(
+    line: &'a str,
+    separator: &'a str,
+    id_index: usize,
+) -> (&'a str, &'a str) {
+    let id = seq_id_range(line, separator, id_index);
+    let rest = line[id.end..]
+        .split_once(char::is_whitespace)
+        .map(|(_, rest)| rest.trim_start())
+        .unwrap_or("");
+    (&line[id], rest)
+}
+
+/// The residue alphabets sequences can be validated against.
+///
+/// All alphabets are case-insensitive. Gap characters are not part of any alphabet.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Alphabet {
+    /// `ACGT` and `N`.
+    Dna,
+    /// `ACGT` and the IUPAC ambiguity codes `RYSWKMBDHVN`.
+    DnaAmbiguous,
+    /// `ACGU` and `N`.
+    Rna,
+    /// The 20 standard amino acids, `BZJUOX` and the stop symbol `*`.
+    Protein,
+}
+
+impl Alphabet {
+    /// Checks whether `c` is a residue of this alphabet.
+    pub fn contains(self, c: char) -> bool {
+        let residues = match self {
+            Alphabet::Dna => "ACGTN",
+            Alphabet::DnaAmbiguous => "ACGTRYSWKMBDHVN",
+            Alphabet::Rna => "ACGUN",
+            Alphabet::Protein => "ACDEFGHIKLMNPQRSTVWYBZJUOX*",
+        };
+        c.is_ascii() && residues.contains(c.to_ascii_uppercase())
+    }
+}
+
+/// Checks that all residues of a sequence are in `alphabet`.
+///
+/// Fails with `ErrorKind::InvalidCharacter` at the first residue outside of
+/// the alphabet, reporting the character and its 0-based position.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::{validate_sequence, Alphabet};
+///
+/// assert!(validate_sequence("ACGTN", Alphabet::Dna).is_ok());
+/// assert!(validate_sequence("ACGUN", Alphabet::Dna).is_err());
+/// ```
+pub fn validate_sequence(seq: &str, alphabet: Alphabet) -> Result<(), FastaError> {
+    match first_invalid(seq, alphabet) {
+        Some((c, pos)) => Err(FastaError::parse(
+            ErrorKind::InvalidCharacter,
+            format!(
+                "Invalid character {:?} at position {} for {:?}.",
+                c, pos, alphabet
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+// The first character of `seq` outside of `alphabet` and its 0-based position.
+pub(crate) fn first_invalid(seq: &str, alphabet: Alphabet) -> Option<(char, usize)> {
+    seq.chars()
+        .enumerate()
+        .find(|(_, c)| !alphabet.contains(*c))
+        .map(|(pos, c)| (c, pos))
+}
+
+/// Guesses the alphabet of a sequence from the residues it contains.
+///
+/// Returns the first of `Dna`, `Rna` and `DnaAmbiguous` that contains all
+/// residues, and `Protein` otherwise, e.g. if any of `EFILPQ` occur. Gaps
+/// (`-` and `.`) are ignored. This is only a heuristic: a protein made up
+/// of `A`, `C`, `G` and `T` only, or a short peptide, is detected as DNA.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::{detect_alphabet, Alphabet};
+///
+/// assert_eq!(detect_alphabet("ACGT-NNacgt"), Alphabet::Dna);
+/// assert_eq!(detect_alphabet("ACGUU"), Alphabet::Rna);
+/// assert_eq!(detect_alphabet("MSKGEELFTG"), Alphabet::Protein);
+/// ```
+pub fn detect_alphabet(seq: &str) -> Alphabet {
+    let residues = || seq.chars().filter(|c| *c != '-' && *c != '.');
+    [Alphabet::Dna, Alphabet::Rna, Alphabet::DnaAmbiguous]
+        .iter()
+        .copied()
+        .find(|alphabet| residues().all(|c| alphabet.contains(c)))
+        .unwrap_or(Alphabet::Protein)
+}
+
+/// Computes the fraction of `G` and `C` among the unambiguous nucleotides.
+///
+/// Only `A`, `C`, `G`, `T` and `U` count toward the denominator, so `N`s
+/// and other ambiguity codes do not lower the GC content. Case is ignored.
+/// Returns 0 if the sequence has no unambiguous nucleotides.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::gc_content;
+///
+/// assert_eq!(gc_content("ACgtNNGC"), 4.0 / 6.0);
+/// ```
+pub fn gc_content(seq: &str) -> f64 {
+    let (gc, total) = gc_counts(seq);
+    if total == 0 {
+        0.0
     } else {
-        // remove `>`
-        &line[1..]
+        gc as f64 / total as f64
     }
 }
 
-// Open file in gz or normal mode
-pub fn open(path: &Path) -> Box<dyn std::io::Read> {
-    if let Some(extension) = path.extension() {
-        match extension.to_str().unwrap() {
-            "gz" => {
-                let fin = File::open(path)
-                    .unwrap_or_else(|_| panic!("Could not open path: {}", path.display()));
-                Box::new(MultiGzDecoder::new(BufReader::new(fin)))
+// Counts the `G`s and `C`s, and all unambiguous nucleotides, as in `gc_content`.
+pub(crate) fn gc_counts(seq: &str) -> (usize, usize) {
+    let (mut gc, mut total) = (0usize, 0usize);
+    for b in seq.bytes() {
+        match b.to_ascii_uppercase() {
+            b'G' | b'C' => {
+                gc += 1;
+                total += 1;
             }
-            _ => Box::new(BufReader::new(File::open(path).unwrap_or_else(|_| {
-                panic!("Could not open path: {}", path.display())
-            }))),
+            b'A' | b'T' | b'U' => total += 1,
+            _ => {}
         }
+    }
+    (gc, total)
+}
+
+/// Counts how often each character occurs in a sequence.
+///
+/// Characters are counted as they are, so lowercase (e.g. soft-masked)
+/// residues are counted separately from uppercase ones.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::base_composition;
+///
+/// let composition = base_composition("ACGTNacGA");
+/// assert_eq!(composition[&'A'], 2);
+/// assert_eq!(composition[&'a'], 1);
+/// assert_eq!(composition.values().sum::<usize>(), 9);
+/// ```
+pub fn base_composition(seq: &str) -> HashMap<char, usize> {
+    let mut res = HashMap::new();
+    for c in seq.chars() {
+        *res.entry(c).or_insert(0) += 1;
+    }
+    res
+}
+
+/// Computes the reverse complement of a nucleotide sequence.
+///
+/// Supports the IUPAC nucleotide alphabet including ambiguity codes and
+/// gaps, and preserves case. `U` is complemented to `A`, while `A` is
+/// always complemented to `T`. Other characters are reported as an error.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::reverse_complement;
+///
+/// assert_eq!(reverse_complement("AACGtn").unwrap(), "naCGTT");
+/// assert!(reverse_complement("ACXT").is_err());
+/// ```
+pub fn reverse_complement(seq: &str) -> Result<String, FastaError> {
+    // validate in order, so that errors name the first invalid character
+    Ok(complement(seq)?.chars().rev().collect())
+}
+
+/// Computes the complement of a nucleotide sequence, without reversing it.
+///
+/// Bases are complemented like in `reverse_complement`.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::complement;
+///
+/// assert_eq!(complement("AACGtn").unwrap(), "TTGCan");
+/// ```
+pub fn complement(seq: &str) -> Result<String, FastaError> {
+    seq.chars()
+        .enumerate()
+        .map(|(i, c)| complement_base(i, c))
+        .collect()
+}
+
+// Complements the nucleotide `c` at the 0-based residue position `i`, preserving case.
+fn complement_base(i: usize, c: char) -> Result<char, FastaError> {
+    let complement = match c.to_ascii_uppercase() {
+        'A' => 'T',
+        'T' | 'U' => 'A',
+        'G' => 'C',
+        'C' => 'G',
+        'R' => 'Y',
+        'Y' => 'R',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        'S' | 'W' | 'N' | '-' | '.' | '*' => c.to_ascii_uppercase(),
+        _ => {
+            return Err(FastaError::parse(
+                ErrorKind::InvalidCharacter,
+                format!("Invalid nucleotide {:?} at position {}.", c, i),
+            ))
+        }
+    };
+    if c.is_ascii_lowercase() {
+        Ok(complement.to_ascii_lowercase())
     } else {
-        Box::new(BufReader::new(File::open(path).unwrap_or_else(|_| {
-            panic!("Could not open path: {}", path.display())
-        })))
+        Ok(complement)
     }
 }
 
+/// Parses `key=value` style attributes from a description line.
+///
+/// Attributes are separated by `pair_sep` and split into key and value at the
+/// first `kv_sep`. A leading `>` and the id (the first whitespace-delimited
+/// field, unless it already contains `kv_sep`) are skipped. Values may be
+/// wrapped in double quotes to contain either separator; the quotes are removed.
+/// Pairs without `kv_sep` are ignored, so a description without attributes
+/// yields an empty map.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::parse_attributes;
+///
+/// let attrs = parse_attributes(">chr1 ID=gene1;Note=\"a;b\"", '=', ';');
+/// assert_eq!(attrs["ID"], "gene1");
+/// assert_eq!(attrs["Note"], "a;b");
+/// ```
+pub fn parse_attributes(
+    description: &str,
+    kv_sep: char,
+    pair_sep: char,
+) -> HashMap<String, String> {
+    let line = description.strip_prefix('>').unwrap_or(description).trim();
+    let attributes = match line.split_once(char::is_whitespace) {
+        Some((id, rest)) if !id.contains(kv_sep) => rest,
+        _ if line.contains(kv_sep) => line,
+        _ => "",
+    };
+
+    let mut res = HashMap::new();
+    for pair in split_unquoted(attributes, pair_sep) {
+        // the first field is always present; the value is whatever follows it
+        let key = split_unquoted(pair, kv_sep).next().unwrap();
+        let value = match pair.get(key.len() + kv_sep.len_utf8()..) {
+            Some(v) => v.trim(),
+            None => continue,
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            continue;
+        }
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        res.insert(key.to_string(), value.to_string());
+    }
+    res
+}
+
+/// Parses GFF3-style attributes (`key=value;key=value`) from a description line.
+pub fn parse_gff_attributes(description: &str) -> HashMap<String, String> {
+    parse_attributes(description, '=', ';')
+}
+
+// Split at `sep`, ignoring occurrences within double quotes.
+fn split_unquoted(s: &str, sep: char) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    s.split(move |c| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == sep && !in_quotes
+    })
+}
+
+/// Hashes a sequence with 64-bit FNV-1a, ignoring case.
+pub(crate) fn sequence_hash(seq: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in seq.bytes() {
+        hash ^= u64::from(b.to_ascii_uppercase());
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+// Passes each description line of a file, without its line break, to `f`
+// until it returns `false`. Sequence lines are skipped within the read buffer,
+// without copying or decoding them, and so are lines before the first description.
+pub(crate) fn scan_descriptions<F>(path: &Path, mut f: F) -> Result<(), FastaError>
+where
+    F: FnMut(&str) -> Result<bool, FastaError>,
+{
+    let mut reader = BufReader::new(try_open(path)?);
+    let mut header = Vec::new();
+    // false while skipping a line longer than the read buffer
+    let mut at_line_start = true;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        if at_line_start && buf[0] == b'>' {
+            header.clear();
+            reader.read_until(b'\n', &mut header)?;
+            let line = std::str::from_utf8(&header)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+                .trim_end_matches(&['\n', '\r'][..]);
+            if !f(line)? {
+                break;
+            }
+        } else {
+            // skip the line without copying it
+            match buf.iter().position(|b| *b == b'\n') {
+                Some(i) => {
+                    reader.consume(i + 1);
+                    at_line_start = true;
+                }
+                None => {
+                    let len = buf.len();
+                    reader.consume(len);
+                    at_line_start = false;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Open file in gz, zstd or normal mode
+pub fn open(path: &Path) -> Box<dyn std::io::Read> {
+    try_open(path).unwrap_or_else(|_| panic!("Could not open path: {}", path.display()))
+}
+
+// Open file in gz, zstd or normal mode, without panicking
+pub(crate) fn try_open(path: &Path) -> std::io::Result<Box<dyn std::io::Read>> {
+    decompress(BufReader::new(File::open(path)?), Some(path))
+}
+
+// Wraps `reader` in a decoder for its compression, see `detect_compression`.
+pub(crate) fn decompress<'a, R: BufRead + 'a>(
+    reader: R,
+    path: Option<&Path>,
+) -> std::io::Result<Box<dyn std::io::Read + 'a>> {
+    let (compression, reader) = detect_compression(reader, path)?;
+    decoder(reader, compression)
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// The compression of an input file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Compression {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+// A reader whose first bytes were read and are put back in front of it.
+pub(crate) type Rewound<R> = Chain<Cursor<Vec<u8>>, R>;
+
+// Detects the compression of `reader` from its magic bytes, and returns it
+// with a reader over the whole input, magic bytes included. The extension of
+// `path`, if any, decides only if the input is too short to tell.
+pub(crate) fn detect_compression<R: BufRead>(
+    mut reader: R,
+    path: Option<&Path>,
+) -> std::io::Result<(Compression, Rewound<R>)> {
+    let start = read_magic(&mut reader)?;
+    let compression = compression_of(&start, path);
+    Ok((compression, Cursor::new(start).chain(reader)))
+}
+
+// Reads the first bytes of `reader` that may hold a magic number, fewer only
+// at its end, however little each read returns.
+fn read_magic<R: Read>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut start = Vec::with_capacity(ZSTD_MAGIC.len());
+    reader
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut start)?;
+    Ok(start)
+}
+
+// Detects the compression of an input from its first bytes, see `detect_compression`.
+pub(crate) fn compression_of(start: &[u8], path: Option<&Path>) -> Compression {
+    if start.starts_with(&GZIP_MAGIC) {
+        Compression::Gzip
+    } else if start.starts_with(&ZSTD_MAGIC) {
+        Compression::Zstd
+    } else if start.len() < ZSTD_MAGIC.len() {
+        match path
+            .and_then(|path| path.extension())
+            .and_then(|e| e.to_str())
+        {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::Plain,
+        }
+    } else {
+        Compression::Plain
+    }
+}
+
+// Opens the file at `path` and detects its compression, see `detect_compression`.
+// The returned reader starts at the beginning of the file.
+pub(crate) fn open_detected(path: &Path) -> std::io::Result<(Compression, BufReader<File>)> {
+    let mut file = File::open(path)?;
+    let compression = compression_of(&read_magic(&mut file)?, Some(path));
+    file.seek(SeekFrom::Start(0))?;
+    Ok((compression, BufReader::new(file)))
+}
+
+// Detects the compression of the file at `path`, see `detect_compression`.
+pub(crate) fn file_compression(path: &Path) -> std::io::Result<Compression> {
+    Ok(open_detected(path)?.0)
+}
+
+// Wraps `reader` in a decoder for `compression`.
+pub(crate) fn decoder<'a, R: BufRead + 'a>(
+    reader: R,
+    compression: Compression,
+) -> std::io::Result<Box<dyn std::io::Read + 'a>> {
+    match compression {
+        Compression::Plain => Ok(Box::new(reader)),
+        Compression::Gzip => Ok(Box::new(MultiGzDecoder::new(reader))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(zstd::Decoder::with_buffer(reader)?)),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err(zstd_unsupported()),
+    }
+}
+
+// The error for zstd input without the `zstd` feature.
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn zstd_unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "Reading zstd compressed input requires the `zstd` feature.",
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,10 +649,189 @@ mod tests {
         assert_eq!(seq_id_from_description(descr, "|", 1), "Q2HZH0");
     }
 
+    #[test]
+    fn complements() {
+        assert_eq!(reverse_complement("acgTU").unwrap(), "AAcgt");
+        assert_eq!(reverse_complement("RYSWKMBDHVN").unwrap(), "NBDHVKMWSRY");
+        assert_eq!(complement("RYSWKMbdhvn").unwrap(), "YRSWMKvhdbn");
+        // palindromic restriction site
+        assert_eq!(reverse_complement("GAATTC").unwrap(), "GAATTC");
+        assert_eq!(reverse_complement("").unwrap(), "");
+        match complement("ACXT") {
+            Err(FastaError::Parse { kind, message }) => {
+                assert_eq!(kind, ErrorKind::InvalidCharacter);
+                assert!(message.contains("'X' at position 2"));
+            }
+            other => panic!("expected InvalidCharacter error, got {:?}", other),
+        }
+        // the first invalid character is reported, at its residue position
+        for seq in ["AXCZ", "AXCé"] {
+            match reverse_complement(seq) {
+                Err(FastaError::Parse { message, .. }) => {
+                    assert!(message.contains("'X' at position 1"))
+                }
+                other => panic!("expected InvalidCharacter error, got {:?}", other),
+            }
+        }
+        match reverse_complement("GAéX") {
+            Err(FastaError::Parse { message, .. }) => {
+                assert!(message.contains("'é' at position 2"))
+            }
+            other => panic!("expected InvalidCharacter error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn seq_id_index_out_of_range() {
+        let descr = ">sp|Q2HZH0";
+        assert_eq!(seq_id_from_description(descr, "|", 3), "sp|Q2HZH0");
+        match checked_seq_id(descr, "|", 3) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::IdIndexOutOfRange),
+            other => panic!("expected IdIndexOutOfRange error, got {:?}", other),
+        }
+        assert_eq!(checked_seq_id(descr, "|", 1).unwrap(), "Q2HZH0");
+
+        // heterogeneous headers fail instead of panicking
+        let path = std::env::temp_dir().join("fasta_rs_heterogeneous.fasta");
+        std::fs::write(&path, ">sp|P1|A_HUMAN\nAC\n>sp|P2\nGG\n").unwrap();
+        assert!(crate::index::FastaIndex::try_new(&path, "|", 2).is_err());
+        assert!(crate::pieces::FastaLengths::try_from_fasta(&path, "|", 2).is_err());
+        assert!(crate::pieces::FastaAccessions::try_from_fasta(&path, "|", 2).is_err());
+    }
+
+    #[test]
+    fn alphabet_validation_and_detection() {
+        match validate_sequence("MSKGQE", Alphabet::DnaAmbiguous) {
+            Err(FastaError::Parse { kind, message }) => {
+                assert_eq!(kind, ErrorKind::InvalidCharacter);
+                assert!(message.contains("'Q' at position 4"));
+            }
+            other => panic!("expected InvalidCharacter error, got {:?}", other),
+        }
+        assert!(validate_sequence("acgtrykm", Alphabet::DnaAmbiguous).is_ok());
+        assert_eq!(detect_alphabet(""), Alphabet::Dna);
+        assert_eq!(detect_alphabet("ACGTRY"), Alphabet::DnaAmbiguous);
+        assert_eq!(detect_alphabet("ACGTX"), Alphabet::Protein);
+        // a peptide of alanine, cysteine, glycine and threonine looks like DNA
+        let peptide = "GATCAGTCCAGT";
+        assert!(validate_sequence(peptide, Alphabet::Protein).is_ok());
+        assert_eq!(detect_alphabet(peptide), Alphabet::Dna);
+    }
+
+    #[test]
+    fn seq_id_first_field() {
+        let descr = ">Q2HZH0 Interleukin-1 beta";
+        assert_eq!(seq_id_from_description(descr, " ", 0), "Q2HZH0");
+        assert_eq!(seq_id_from_description(&descr[1..], " ", 0), "Q2HZH0");
+        assert_eq!(seq_id_from_description(">Q2HZH0", " ", 0), "Q2HZH0");
+        assert_eq!(seq_id_from_description("Q2HZH0", " ", 0), "Q2HZH0");
+    }
+
+    #[test]
+    fn seq_id_surrounding_whitespace() {
+        assert_eq!(seq_id_from_description("> Q2HZH0", " ", 0), "Q2HZH0");
+        assert_eq!(seq_id_from_description(">Q2HZH0 ", " ", 0), "Q2HZH0");
+        assert_eq!(seq_id_from_description("> Q2HZH0", "|", 1), "Q2HZH0");
+        assert_eq!(
+            seq_id_from_description(">sp| Q2HZH0 |IL1B", "|", 1),
+            "Q2HZH0"
+        );
+        assert_eq!(seq_id_from_description(">Q2HZH0 desc\r", " ", 0), "Q2HZH0");
+    }
+
+    #[test]
+    fn tagged_fields_from_descr() {
+        let fields = parse_tagged_fields(">id SS:HHHEEECC CONF:9987 POS:3:12", ' ');
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields["SS"], "HHHEEECC");
+        assert_eq!(fields["CONF"], "9987");
+        assert_eq!(fields["POS"], "3:12");
+        let fields = parse_tagged_fields(">id|SS:HHH|some text: not a tag|", '|');
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields["SS"], "HHH");
+        assert!(parse_tagged_fields(">sp|Q2HZH0|IL1B_PUSHI", '|').is_empty());
+    }
+
+    #[test]
+    fn xrefs_from_descr() {
+        let pairs = |v: &[(&str, &str)]| {
+            v.iter()
+                .map(|(db, acc)| (db.to_string(), acc.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            parse_xrefs(">gi|12345|ref|NP_001.1|sp|P00000|"),
+            pairs(&[("gi", "12345"), ("ref", "NP_001.1"), ("sp", "P00000")])
+        );
+        assert_eq!(
+            parse_xrefs(">sp|Q2HZH0|IL1B_PUSHI Interleukin-1 beta OS=Pusa hispida"),
+            pairs(&[("sp", "Q2HZH0")])
+        );
+        assert_eq!(parse_xrefs("gi|12345"), pairs(&[("gi", "12345")]));
+        assert!(parse_xrefs(">Q2HZH0 a|b").is_empty());
+        assert!(parse_xrefs(">").is_empty());
+    }
+
     #[test]
     fn seq_id_long_descr() {
         let descr =
             ">sp|Q2HZH0|IL1B_PUSHI Interleukin-1 beta OS=Pusa hispida OX=9718 GN=IL1B PE=2 SV=1";
         assert_eq!(seq_id_from_description(descr, "|", 1), "Q2HZH0");
     }
+
+    #[test]
+    fn id_and_rest_from_uniprot_descr() {
+        let descr =
+            ">sp|Q2HZH0|IL1B_PUSHI Interleukin-1 beta OS=Pusa hispida OX=9718 GN=IL1B PE=2 SV=1";
+        assert_eq!(
+            split_id_and_rest(descr, "|", 1),
+            (
+                "Q2HZH0",
+                "Interleukin-1 beta OS=Pusa hispida OX=9718 GN=IL1B PE=2 SV=1"
+            )
+        );
+        assert_eq!(split_id_and_rest(">Q2HZH0", "|", 1), ("Q2HZH0", ""));
+        assert_eq!(
+            split_id_and_rest(">sp|Q2HZH0|IL1B_PUSHI", "|", 2),
+            ("IL1B_PUSHI", "")
+        );
+        // the rest starts after the id, not after the first field
+        assert_eq!(split_id_and_rest(">x ID text", " ", 1), ("ID", "text"));
+        assert_eq!(
+            split_id_and_rest(">x  ID  more text ", " ", 2),
+            ("ID", "more text ")
+        );
+    }
+
+    #[test]
+    fn attributes_from_description() {
+        let attrs = parse_gff_attributes(">seq1 ID=gene1;Name=IL1B");
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs["ID"], "gene1");
+        assert_eq!(attrs["Name"], "IL1B");
+        assert!(parse_gff_attributes(">Q2HZH0").is_empty());
+        assert!(parse_gff_attributes(">Q2HZH0 Interleukin-1 beta").is_empty());
+    }
+
+    #[test]
+    fn attributes_with_quoted_separators() {
+        let attrs = parse_attributes(
+            r#">seq1 product="kinase; putative";note="a=b";id=7"#,
+            '=',
+            ';',
+        );
+        assert_eq!(attrs["product"], "kinase; putative");
+        assert_eq!(attrs["note"], "a=b");
+        assert_eq!(attrs["id"], "7");
+
+        let attrs = parse_attributes(r#"k1:"x,y",k2:z"#, ':', ',');
+        assert_eq!(attrs["k1"], "x,y");
+        assert_eq!(attrs["k2"], "z");
+    }
+
+    #[test]
+    fn sequence_hash_ignores_case() {
+        assert_eq!(sequence_hash("ACGTN"), sequence_hash("acgtn"));
+        assert_ne!(sequence_hash("ACGT"), sequence_hash("TGCA"));
+    }
 }
@@ -1,15 +1,43 @@
 //! An index that stores byte offsets of individual entries
 //! in FASTA files.
 
-use crate::helpers::seq_id_from_description;
+use crate::errors::{ErrorKind, FastaError};
+#[cfg(feature = "regex")]
+use crate::helpers::empty_id_error;
+use crate::helpers::{checked_seq_id, reverse_complement, seek_on_compressed_error};
+use crate::pieces::FastaEntry;
 use crate::read::FastaHandle;
 
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+#[cfg(feature = "regex")]
+use regex::Regex;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::{read_to_string, File};
-use std::io::{BufRead, BufReader, BufWriter, Error};
+use std::io::{BufRead, BufReader, BufWriter, Error, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 use std::path::Path;
 
+/// The strand of a nucleotide sequence region.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Strand {
+    Plus,
+    Minus,
+}
+
+/// How to handle an id that occurs in more than one entry when building an index.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Keep the first entry with the id.
+    FirstWins,
+    /// Keep the last entry with the id.
+    LastWins,
+    /// Fail with `ErrorKind::DuplicateId`.
+    Error,
+}
+
 /// An index into FASTA files.
 ///
 /// Wraps a sequence id -> byte offset mapping. The sequence accessions
@@ -29,50 +57,370 @@ use std::path::Path;
 /// // create the index
 /// let index = FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1);
 /// // write to file
-/// index.to_json(Path::new("./resources/test.index")).expect("Failed to dump json.");
+/// let outpath = std::env::temp_dir().join("fasta_index_doctest.index");
+/// index.to_json(&outpath).expect("Failed to dump json.");
 /// // load from json
-/// assert_eq!(index, FastaIndex::from_json(Path::new("./resources/test.index")).unwrap());
+/// assert_eq!(index, FastaIndex::from_json(&outpath).unwrap());
 /// ```
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct FastaIndex {
     pub id_to_offset: HashMap<String, u64>,
+    /// Sequence layouts, recorded by `new_with_lengths` for entries whose
+    /// sequence lines are wrapped to a uniform length, or loaded with `from_fai`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub id_to_layout: HashMap<String, SequenceLayout>,
+    /// Sequence lengths, recorded by `new_with_lengths`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub id_to_length: HashMap<String, usize>,
+}
+
+/// Where and how a sequence is stored in a FASTA file, as recorded in
+/// a samtools `.fai` index.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SequenceLayout {
+    /// The number of residues.
+    pub length: u64,
+    /// The byte offset of the first residue, after the description line.
+    pub offset: u64,
+    /// The number of residues per line.
+    pub line_bases: u64,
+    /// The number of bytes per line, including the line break.
+    pub line_width: u64,
+}
+
+impl SequenceLayout {
+    // The layout of `length` residues at `offset` wrapped to `width` residues
+    // per line with `\n` line breaks, as written by `FastaWriter`.
+    pub(crate) fn wrapped(length: u64, offset: u64, width: u64) -> Self {
+        let line_bases = match width {
+            0 => length,
+            width => width.min(length),
+        };
+        SequenceLayout {
+            length,
+            offset,
+            line_bases,
+            line_width: if line_bases == 0 { 0 } else { line_bases + 1 },
+        }
+    }
+
+    /// Computes the byte offset of the 0-based position `pos` in the sequence.
+    pub fn byte_offset(&self, pos: u64) -> u64 {
+        if self.line_bases == 0 {
+            // empty sequence
+            return self.offset;
+        }
+        self.offset + pos / self.line_bases * self.line_width + pos % self.line_bases
+    }
 }
 
 impl FastaIndex {
     pub fn new(path: &Path, separator: &str, id_index: usize) -> Self {
-        let mut res = HashMap::new();
+        Self::try_new(path, separator, id_index).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let fasta_handle = FastaHandle::open_fasta(path);
-        if let FastaHandle::Compressed(_) = fasta_handle {
-            panic!(
-                "Tried to build index on non seekable compressed file: {:?}",
-                path
-            );
+    /// Like `new`, but returns an error instead of panicking on unreadable
+    /// files, duplicate ids or empty ids.
+    pub fn try_new(path: &Path, separator: &str, id_index: usize) -> Result<Self, FastaError> {
+        Self::new_with_policy(path, separator, id_index, DuplicatePolicy::Error)
+    }
+
+    /// Like `try_new`, but also records the length and, for uniformly wrapped
+    /// entries, the line layout of each sequence.
+    ///
+    /// Lengths are needed by `length` and `FastaLengths::from_index`, layouts
+    /// by `to_fai` and `base_offset`; with layouts, `get_region` seeks to the
+    /// requested residues directly. Both are stored with the index and add
+    /// to its size, so they are only recorded on request.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::path::Path;
+    ///
+    /// let path = Path::new("./resources/test.fasta");
+    /// assert_eq!(FastaIndex::new(path, "|", 1).length("P93158"), None);
+    /// let index = FastaIndex::new_with_lengths(path, "|", 1).unwrap();
+    /// assert_eq!(index.length("P93158"), Some(120));
+    /// ```
+    pub fn new_with_lengths(
+        path: &Path,
+        separator: &str,
+        id_index: usize,
+    ) -> Result<Self, FastaError> {
+        Self::build(path, DuplicatePolicy::Error, true, |line| {
+            Ok(vec![checked_seq_id(line, separator, id_index)?])
+        })
+    }
+
+    /// Like `try_new`, but handles ids that occur in more than one entry
+    /// according to `policy`.
+    ///
+    /// With `DuplicatePolicy::Error`, the error names the id and the offsets
+    /// of both entries.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::{DuplicatePolicy, FastaIndex};
+    /// use std::path::Path;
+    ///
+    /// let path = std::env::temp_dir().join("fasta_duplicates_doctest.fasta");
+    /// std::fs::write(&path, ">a\nAC\n>a\nGGT\n").unwrap();
+    /// let index = FastaIndex::new_with_policy(&path, " ", 0, DuplicatePolicy::LastWins).unwrap();
+    /// assert_eq!(index.id_to_offset["a"], 6);
+    /// let index = FastaIndex::new_with_policy(&path, " ", 0, DuplicatePolicy::FirstWins).unwrap();
+    /// assert_eq!(index.id_to_offset["a"], 0);
+    /// assert!(FastaIndex::new_with_policy(&path, " ", 0, DuplicatePolicy::Error).is_err());
+    /// ```
+    pub fn new_with_policy(
+        path: &Path,
+        separator: &str,
+        id_index: usize,
+        policy: DuplicatePolicy,
+    ) -> Result<Self, FastaError> {
+        Self::build(path, policy, false, |line| {
+            Ok(vec![checked_seq_id(line, separator, id_index)?])
+        })
+    }
+
+    /// Creates an index with an entry for each of several ids per description.
+    ///
+    /// Each `(separator, id_index)` pair extracts one id as in `new`, and all
+    /// ids of an entry point to its offset, so that e.g. UniProt entries can
+    /// be looked up by accession and by entry name. It is an error if an id
+    /// occurs more than once, whether in different entries or in different
+    /// fields of the same entry.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::path::Path;
+    ///
+    /// let path = std::env::temp_dir().join("fasta_multi_field_doctest.fasta");
+    /// std::fs::write(&path, ">sp|P01308|INS_HUMAN\nMALWMRLLPL\n").unwrap();
+    /// let index = FastaIndex::new_multi_field(&path, &[("|", 1), ("|", 2)]).unwrap();
+    /// assert_eq!(index.id_to_offset["P01308"], index.id_to_offset["INS_HUMAN"]);
+    /// ```
+    pub fn new_multi_field(path: &Path, extractors: &[(&str, usize)]) -> Result<Self, FastaError> {
+        Self::build(path, DuplicatePolicy::Error, false, |line| {
+            extractors
+                .iter()
+                .map(|(separator, id_index)| checked_seq_id(line, separator, *id_index))
+                .collect()
+        })
+    }
+
+    /// Creates an index keyed by the first capture group of `re`.
+    ///
+    /// The regex is matched against each description line without the
+    /// leading `>`. It is an error if it does not match a description,
+    /// or if the captured id is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use regex::Regex;
+    /// use std::path::Path;
+    ///
+    /// let re = Regex::new(r"GN=(\S+)").unwrap();
+    /// let index = FastaIndex::new_with_regex(Path::new("./resources/test.fasta"), &re).unwrap();
+    /// assert!(index.id_to_offset.contains_key("TGFBR1"));
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn new_with_regex(path: &Path, re: &Regex) -> Result<Self, FastaError> {
+        Self::build(path, DuplicatePolicy::Error, false, |line| {
+            let captures = re.captures(&line[1..]).ok_or_else(|| {
+                FastaError::parse(
+                    ErrorKind::NoIdMatch,
+                    format!("Id pattern does not match description line: {:?}", line),
+                )
+            })?;
+            match captures.get(1) {
+                Some(id) if !id.as_str().trim().is_empty() => Ok(vec![id.as_str()]),
+                _ => Err(empty_id_error(line)),
+            }
+        })
+    }
+
+    /// Like `try_new`, but saves the progress to `checkpoint` every `interval` bytes.
+    ///
+    /// If indexing is interrupted, `resume_from` continues from the last
+    /// checkpoint instead of starting over. The checkpoint holds the partial
+    /// index, so saving it takes time proportional to the entries found so
+    /// far; choose `interval` accordingly. It is written to a temporary file
+    /// first, so an interruption while saving keeps the previous checkpoint.
+    /// The checkpoint is removed once the index is complete.
+    pub fn new_with_checkpoints(
+        path: &Path,
+        separator: &str,
+        id_index: usize,
+        checkpoint: &Path,
+        interval: u64,
+    ) -> Result<Self, FastaError> {
+        let (file_len, modified) = file_fingerprint(path)?;
+        let state = IndexCheckpoint {
+            offset: 0,
+            id_to_offset: HashMap::new(),
+            separator: separator.to_string(),
+            id_index,
+            interval,
+            file_len,
+            modified,
+        };
+        Self::build_with_checkpoints(path, state, checkpoint)
+    }
+
+    /// Continues indexing `path` from a checkpoint of `new_with_checkpoints`.
+    ///
+    /// Fails with `ErrorKind::FileChanged` if the size or modification time
+    /// of the file differ from when the checkpoint was written.
+    pub fn resume_from(checkpoint: &Path, path: &Path) -> Result<Self, FastaError> {
+        let state: IndexCheckpoint =
+            serde_json::from_reader(BufReader::new(File::open(checkpoint)?))
+                .map_err(Error::from)?;
+        if file_fingerprint(path)? != (state.file_len, state.modified) {
+            return Err(FastaError::parse(
+                ErrorKind::FileChanged,
+                format!("{:?} changed since the checkpoint was written.", path),
+            ));
         }
+        Self::build_with_checkpoints(path, state, checkpoint)
+    }
+
+    fn build_with_checkpoints(
+        path: &Path,
+        mut state: IndexCheckpoint,
+        checkpoint: &Path,
+    ) -> Result<Self, FastaError> {
+        let separator = state.separator.clone();
+        let id_index = state.id_index;
+        let partial = FastaIndex {
+            id_to_offset: std::mem::take(&mut state.id_to_offset),
+            id_to_layout: HashMap::new(),
+            id_to_length: HashMap::new(),
+        };
+        let mut next_checkpoint = state.offset.saturating_add(state.interval);
+        let res = Self::scan(
+            path,
+            state.offset,
+            partial,
+            DuplicatePolicy::Error,
+            false,
+            |line| Ok(vec![checked_seq_id(line, &separator, id_index)?]),
+            |offset, partial| {
+                if offset >= next_checkpoint {
+                    state.offset = offset;
+                    state.save(checkpoint, partial)?;
+                    next_checkpoint = offset.saturating_add(state.interval);
+                }
+                Ok(())
+            },
+        )?;
+        match std::fs::remove_file(checkpoint) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(res),
+        }
+    }
+
+    // Scans the file for description lines and indexes them by the ids `get_ids` extracts,
+    // recording sequence lengths and layouts if `lengths` is set.
+    fn build<F>(
+        path: &Path,
+        policy: DuplicatePolicy,
+        lengths: bool,
+        get_ids: F,
+    ) -> Result<Self, FastaError>
+    where
+        F: for<'a> Fn(&'a str) -> Result<Vec<&'a str>, FastaError>,
+    {
+        let empty = FastaIndex {
+            id_to_offset: HashMap::new(),
+            id_to_layout: HashMap::new(),
+            id_to_length: HashMap::new(),
+        };
+        Self::scan(path, 0, empty, policy, lengths, get_ids, |_, _| Ok(()))
+    }
+
+    // Like `build`, starting at `global_offset` with the entries in `res`.
+    // `progress` is called with the offset of each description line, when
+    // all entries before it are complete.
+    fn scan<F, P>(
+        path: &Path,
+        mut global_offset: u64,
+        mut res: FastaIndex,
+        policy: DuplicatePolicy,
+        lengths: bool,
+        get_ids: F,
+        mut progress: P,
+    ) -> Result<Self, FastaError>
+    where
+        F: for<'a> Fn(&'a str) -> Result<Vec<&'a str>, FastaError>,
+        P: FnMut(u64, &FastaIndex) -> Result<(), FastaError>,
+    {
+        let mut fasta_handle = FastaHandle::try_open_fasta(path)?;
+        if !matches!(fasta_handle, FastaHandle::Uncompressed(_)) {
+            return Err(seek_on_compressed_error(path));
+        }
+        fasta_handle.seek(SeekFrom::Start(global_offset))?;
         let mut reader = BufReader::new(fasta_handle);
         let mut line_buf = String::new();
-        let mut global_offset: u64 = 0;
+        let mut entry: Option<EntryScan> = None;
 
-        let mut len = reader
-            .read_line(&mut line_buf)
-            .expect("Failed to read line!");
+        let mut len = reader.read_line(&mut line_buf)?;
         while len != 0 {
-            if line_buf.starts_with('>') {
-                line_buf.pop();
-                let key = seq_id_from_description(&line_buf, separator, id_index);
-                if let Some(_old_entry) = res.insert(key.to_string(), global_offset) {
-                    panic!("Multiple entries found for id: {:?}", key);
-                };
+            // the last line of a file may lack a line break
+            let line = line_buf.trim_end_matches(&['\n', '\r'][..]);
+            if line.starts_with('>') {
+                if let Some(entry) = entry.take() {
+                    entry.finish(&mut res);
+                }
+                progress(global_offset, &res)?;
+                let mut keys = Vec::new();
+                for key in get_ids(line)? {
+                    match res.id_to_offset.entry(key.to_string()) {
+                        Entry::Vacant(vacant) => {
+                            vacant.insert(global_offset);
+                        }
+                        Entry::Occupied(mut occupied) => match policy {
+                            DuplicatePolicy::FirstWins => continue,
+                            DuplicatePolicy::LastWins => {
+                                occupied.insert(global_offset);
+                            }
+                            DuplicatePolicy::Error => {
+                                return Err(FastaError::parse(
+                                    ErrorKind::DuplicateId,
+                                    format!(
+                                        "Multiple entries found for id {:?}, at offsets {} and {}.",
+                                        key,
+                                        occupied.get(),
+                                        global_offset
+                                    ),
+                                ))
+                            }
+                        },
+                    }
+                    keys.push(key.to_string());
+                }
+                if lengths {
+                    entry = Some(EntryScan::new(keys, global_offset + len as u64));
+                }
+            } else if let Some(entry) = entry.as_mut() {
+                if line.starts_with(';') {
+                    entry.push_comment(len as u64);
+                } else {
+                    entry.push_line(line.len() as u64, len as u64);
+                }
             }
 
             global_offset += len as u64;
             line_buf.clear();
-            len = reader
-                .read_line(&mut line_buf)
-                .expect("Failed to read line!");
+            len = reader.read_line(&mut line_buf)?;
+        }
+        if let Some(entry) = entry {
+            entry.finish(&mut res);
         }
 
-        FastaIndex { id_to_offset: res }
+        Ok(res)
     }
 
     pub fn from_json(path: &Path) -> Result<Self, Error> {
@@ -81,18 +429,990 @@ impl FastaIndex {
         Ok(res)
     }
 
+    /// Loads a `.fai` index as written by `samtools faidx`.
+    ///
+    /// A `.fai` index records where the sequence of each entry starts rather
+    /// than its description line, so the resulting index only holds sequence
+    /// layouts: `get_region` and the methods built on it work with it,
+    /// methods that read whole entries by offset do not. Region queries seek
+    /// directly to the requested residues, accounting for line breaks.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::path::Path;
+    ///
+    /// let fasta = std::env::temp_dir().join("fasta_faidx_doctest.fasta");
+    /// let fai = std::env::temp_dir().join("fasta_faidx_doctest.fasta.fai");
+    /// std::fs::write(&fasta, ">chr1\nACGT\nTTGG\nCC\n").unwrap();
+    /// std::fs::write(&fai, "chr1\t10\t6\t4\t5\n").unwrap();
+    /// let index = FastaIndex::from_fai(&fai).unwrap();
+    /// let region = index.get_region(&fasta, "chr1", 2, 9).unwrap();
+    /// assert_eq!(region.sequence, "GTTTGGC");
+    /// ```
+    pub fn from_fai(path: &Path) -> Result<Self, FastaError> {
+        let mut id_to_layout = HashMap::new();
+        for (line_nr, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || {
+                FastaError::parse(
+                    ErrorKind::InvalidIndex,
+                    format!("Malformed line {} in .fai index {:?}.", line_nr + 1, path),
+                )
+            };
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() < 5 {
+                return Err(invalid());
+            }
+            let mut numbers = [0; 4];
+            for (number, field) in numbers.iter_mut().zip(&fields[1..5]) {
+                *number = field.trim().parse().map_err(|_| invalid())?;
+            }
+            let [length, offset, line_bases, line_width] = numbers;
+            // empty sequences have no lines
+            if (line_bases == 0 && length > 0) || line_width < line_bases {
+                return Err(invalid());
+            }
+            let layout = SequenceLayout {
+                length,
+                offset,
+                line_bases,
+                line_width,
+            };
+            if id_to_layout.insert(fields[0].to_string(), layout).is_some() {
+                return Err(FastaError::parse(
+                    ErrorKind::DuplicateId,
+                    format!("Multiple entries found for id: {:?}", fields[0]),
+                ));
+            }
+        }
+        Ok(FastaIndex {
+            id_to_offset: HashMap::new(),
+            id_to_layout,
+            id_to_length: HashMap::new(),
+        })
+    }
+
+    /// Loads a samtools `.fai` index, see `from_fai`.
+    #[deprecated(note = "renamed to `from_fai`")]
+    pub fn from_faidx(path: &Path) -> Result<Self, FastaError> {
+        Self::from_fai(path)
+    }
+
+    /// Writes the index in the `.fai` format of `samtools faidx`, one
+    /// `name\tlength\toffset\tlinebases\tlinewidth` line per id, ordered
+    /// by offset.
+    ///
+    /// The index has to be built with `new_with_lengths`, or loaded with
+    /// `from_fai`. A `.fai` index assumes that all sequence lines of an entry
+    /// except the last have the same length. Entries that are wrapped
+    /// irregularly have no sequence layout, and writing an index with such
+    /// entries fails with `ErrorKind::IrregularLineLengths` without creating
+    /// the file; an index without recorded layouts fails with
+    /// `ErrorKind::InvalidIndex`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::path::Path;
+    ///
+    /// let fasta = std::env::temp_dir().join("fasta_to_fai_doctest.fasta");
+    /// let fai = std::env::temp_dir().join("fasta_to_fai_doctest.fasta.fai");
+    /// std::fs::write(&fasta, ">chr1 first\nACGT\nTTGG\nCC\n>chr2\nGATTACA\n").unwrap();
+    /// FastaIndex::new_with_lengths(&fasta, " ", 0)
+    ///     .unwrap()
+    ///     .to_fai(&fai)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     std::fs::read_to_string(&fai).unwrap(),
+    ///     "chr1\t10\t12\t4\t5\nchr2\t7\t31\t7\t8\n"
+    /// );
+    /// ```
+    pub fn to_fai(&self, outpath: &Path) -> Result<(), Error> {
+        let mut entries = Vec::new();
+        for id in self.id_to_offset.keys().chain(self.id_to_layout.keys()) {
+            match self.id_to_layout.get(id) {
+                Some(layout) => entries.push((id, layout)),
+                None if !self.id_to_length.contains_key(id) => {
+                    return Err(FastaError::parse(
+                        ErrorKind::InvalidIndex,
+                        format!(
+                            "No sequence layout recorded for {:?}; build the index with `new_with_lengths`.",
+                            id
+                        ),
+                    )
+                    .into())
+                }
+                None => {
+                    return Err(FastaError::parse(
+                        ErrorKind::IrregularLineLengths,
+                        format!("No uniform line length known for {:?}.", id),
+                    )
+                    .into())
+                }
+            }
+        }
+        entries.sort_by_key(|(id, layout)| (layout.offset, *id));
+        entries.dedup();
+        let mut file = BufWriter::new(File::create(outpath)?);
+        for (id, layout) in entries {
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}\t{}",
+                id, layout.length, layout.offset, layout.line_bases, layout.line_width
+            )?;
+        }
+        file.flush()
+    }
+
+    /// Loads an index from json lines, as written by `to_jsonl`.
+    pub fn from_jsonl(path: &Path) -> Result<Self, Error> {
+        Self::read_jsonl(path, |_| true)
+    }
+
+    /// Like `load_ids`, for an index in json lines format.
+    pub fn load_ids_jsonl(path: &Path, ids: &HashSet<String>) -> Result<Self, Error> {
+        Self::read_jsonl(path, |id| ids.contains(id))
+    }
+
+    fn read_jsonl<F: Fn(&str) -> bool>(path: &Path, keep: F) -> Result<Self, Error> {
+        let mut id_to_offset = HashMap::new();
+        let mut id_to_layout = HashMap::new();
+        let mut id_to_length = HashMap::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: JsonlRecord = serde_json::from_str(&line)?;
+            if keep(&record.id) {
+                if let Some(length) = record.length {
+                    id_to_length.insert(record.id.clone(), length);
+                }
+                if let Some(layout) = record.layout {
+                    id_to_layout.insert(record.id.clone(), layout);
+                }
+                id_to_offset.insert(record.id, record.offset);
+            }
+        }
+        Ok(FastaIndex {
+            id_to_offset,
+            id_to_layout,
+            id_to_length,
+        })
+    }
+
+    /// Writes the index as json lines, one `{"id":..,"offset":..}` object
+    /// per entry, ordered by offset, with the `length` and `layout` of the
+    /// sequence if they are known.
+    ///
+    /// Unlike a single json object, this format can be appended to, read
+    /// partially and processed with line-based tools.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::path::Path;
+    ///
+    /// let index = FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1);
+    /// let outpath = std::env::temp_dir().join("fasta_index_doctest.jsonl");
+    /// index.to_jsonl(&outpath).unwrap();
+    /// assert_eq!(index, FastaIndex::from_jsonl(&outpath).unwrap());
+    /// ```
+    pub fn to_jsonl(&self, outpath: &Path) -> Result<(), Error> {
+        let mut entries = self.id_to_offset.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(_, offset)| **offset);
+        let mut file = BufWriter::new(File::create(outpath)?);
+        for (id, offset) in entries {
+            serde_json::to_writer(
+                &mut file,
+                &JsonlRecord {
+                    id: id.clone(),
+                    offset: *offset,
+                    length: self.id_to_length.get(id).copied(),
+                    layout: self.id_to_layout.get(id).copied(),
+                },
+            )?;
+            file.write_all(b"\n")?;
+        }
+        file.flush()
+    }
+
+    /// Loads only the entries for `ids` from a json index.
+    ///
+    /// The index file is streamed, and offsets of other ids are skipped
+    /// without being stored, so memory use depends on the number of
+    /// requested ids rather than on the size of the index.
+    /// Requested ids that are missing from the index are ignored.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::collections::HashSet;
+    /// use std::path::Path;
+    ///
+    /// let ids = vec!["P93158".to_string()].into_iter().collect::<HashSet<_>>();
+    /// let index = FastaIndex::load_ids(Path::new("./resources/test.index"), &ids).unwrap();
+    /// assert_eq!(index.id_to_offset.len(), 1);
+    /// ```
+    pub fn load_ids(path: &Path, ids: &HashSet<String>) -> Result<Self, FastaError> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        FilteredIndex(ids)
+            .deserialize(&mut deserializer)
+            .map_err(|e| Error::from(e).into())
+    }
+
     pub fn to_json(&self, outpath: &Path) -> Result<(), Error> {
-        let mut file = BufWriter::new(File::create(&outpath)?);
+        let mut file = BufWriter::new(File::create(outpath)?);
         serde_json::to_writer(&mut file, self)?;
         Ok(())
     }
+
+    /// Finds the entry whose bytes contain the file offset `offset`.
+    ///
+    /// This is the entry with the largest start offset not exceeding `offset`.
+    /// The index does not know where the last entry ends, so offsets past
+    /// the end of the file map to the last entry.
+    /// Each call scans the whole index.
+    pub fn record_at_offset(&self, offset: u64) -> Option<&String> {
+        self.id_to_offset
+            .iter()
+            .filter(|(_, start)| **start <= offset)
+            .max_by_key(|(_, start)| **start)
+            .map(|(id, _)| id)
+    }
+
+    /// The number of residues of the entry `id`.
+    ///
+    /// Returns `None` if `id` is not in the index, or if its length was not
+    /// recorded, i.e. if the index was not built with `new_with_lengths`.
+    pub fn length(&self, id: &str) -> Option<usize> {
+        match self.id_to_length.get(id) {
+            Some(length) => Some(*length),
+            None => self
+                .id_to_layout
+                .get(id)
+                .map(|layout| layout.length as usize),
+        }
+    }
+
+    /// Computes the byte offset of the 0-based residue `base` of the entry `id`,
+    /// accounting for line breaks like `samtools faidx`.
+    ///
+    /// Returns `None` if the index holds no sequence layout for `id`, e.g.
+    /// because its lines are wrapped irregularly, or if `base` is not within
+    /// the sequence.
+    pub fn base_offset(&self, id: &str, base: usize) -> Option<u64> {
+        let layout = self.id_to_layout.get(id)?;
+        let base = base as u64;
+        if base < layout.length {
+            Some(layout.byte_offset(base))
+        } else {
+            None
+        }
+    }
+
+    /// Reads the residues `start..end` (0-based, end exclusive) of the entry `id`.
+    ///
+    /// The range is clamped to the end of the sequence, and only the lines
+    /// needed to cover it are read. The description of the returned entry
+    /// names the extracted region as `>id:start-end` in 1-based, inclusive
+    /// coordinates, like `samtools faidx`.
+    pub fn get_region(
+        &self,
+        path: &Path,
+        id: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<FastaEntry, FastaError> {
+        if let Some(layout) = self.id_to_layout.get(id) {
+            return Self::get_layout_region(path, id, layout, start, end);
+        }
+        let offset = self.id_to_offset.get(id).ok_or_else(|| {
+            FastaError::parse(
+                ErrorKind::IdNotFound,
+                format!("No entry for id {:?} in index.", id),
+            )
+        })?;
+        let mut reader = BufReader::new(File::open(path)?);
+        reader.seek(SeekFrom::Start(*offset))?;
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with('>') {
+            return Err(FastaError::parse(
+                ErrorKind::IndexNotAtDescription,
+                format!(
+                    "No description line found at offset {} for {:?}.",
+                    offset, id
+                ),
+            ));
+        }
+
+        let mut sequence = String::new();
+        let mut line_start = 0;
+        while line_start < end {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let residues = line.trim_end();
+            if residues.is_empty() || residues.starts_with('>') {
+                break;
+            }
+            if residues.starts_with(';') {
+                continue;
+            }
+            let line_end = line_start + residues.len();
+            if line_end > start {
+                let from = start.saturating_sub(line_start);
+                let to = end.min(line_end) - line_start;
+                sequence.push_str(&residues[from..to]);
+            }
+            line_start = line_end;
+        }
+
+        let start = start.min(line_start);
+        Ok(FastaEntry {
+            description: format!(">{}:{}-{}", id, start + 1, start + sequence.len()),
+            sequence,
+        })
+    }
+
+    // Reads a region by computing its byte range from the sequence layout.
+    fn get_layout_region(
+        path: &Path,
+        id: &str,
+        layout: &SequenceLayout,
+        start: usize,
+        end: usize,
+    ) -> Result<FastaEntry, FastaError> {
+        let start = (start as u64).min(layout.length);
+        let end = (end as u64).clamp(start, layout.length);
+        let from = layout.byte_offset(start);
+        let mut bytes = Vec::new();
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(from))?;
+        file.take(layout.byte_offset(end) - from)
+            .read_to_end(&mut bytes)?;
+        bytes.retain(|b| !b.is_ascii_whitespace());
+        let sequence =
+            String::from_utf8(bytes).map_err(|e| Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(FastaEntry {
+            description: format!(">{}:{}-{}", id, start + 1, start as usize + sequence.len()),
+            sequence,
+        })
+    }
+
+    /// Reads a region like `get_region`, reverse complemented if `strand` is `Minus`.
+    ///
+    /// The strand is appended to the description, e.g. `>id:11-20(-)`,
+    /// matching `bedtools getfasta -s`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::{FastaIndex, Strand};
+    /// use std::path::Path;
+    ///
+    /// let path = std::env::temp_dir().join("fasta_stranded_doctest.fasta");
+    /// std::fs::write(&path, ">chr1\nAACCGGTTAC\n").unwrap();
+    /// let index = FastaIndex::new(&path, "|", 1);
+    /// let region = index.get_region_stranded(&path, "chr1", 0, 4, Strand::Minus).unwrap();
+    /// assert_eq!(region.description, ">chr1:1-4(-)");
+    /// assert_eq!(region.sequence, "GGTT");
+    /// ```
+    pub fn get_region_stranded(
+        &self,
+        path: &Path,
+        id: &str,
+        start: usize,
+        end: usize,
+        strand: Strand,
+    ) -> Result<FastaEntry, FastaError> {
+        let mut region = self.get_region(path, id, start, end)?;
+        match strand {
+            Strand::Plus => region.description.push_str("(+)"),
+            Strand::Minus => {
+                region.sequence = reverse_complement(&region.sequence)?;
+                region.description.push_str("(-)");
+            }
+        }
+        Ok(region)
+    }
+
+    /// Reads the residues within `flank` positions of the 0-based position `pos`
+    /// of the entry `id`, i.e. `pos - flank` up to and including `pos + flank`.
+    ///
+    /// The window is clamped at both ends of the sequence instead of failing,
+    /// so positions close to the sequence boundaries yield shorter entries.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use std::path::Path;
+    ///
+    /// let path = Path::new("./resources/test.fasta");
+    /// let index = FastaIndex::new(path, "|", 1);
+    /// let flank = index.get_flank(path, "P93158", 2, 5).unwrap();
+    /// assert_eq!(flank.description, ">P93158:1-8");
+    /// assert_eq!(flank.sequence, "TLKVPVHV");
+    /// ```
+    pub fn get_flank(
+        &self,
+        path: &Path,
+        id: &str,
+        pos: usize,
+        flank: usize,
+    ) -> Result<FastaEntry, FastaError> {
+        self.get_region(
+            path,
+            id,
+            pos.saturating_sub(flank),
+            pos.saturating_add(flank).saturating_add(1),
+        )
+    }
+}
+
+// The ids, length and line layout of the entry being indexed.
+struct EntryScan {
+    keys: Vec<String>,
+    layout: SequenceLayout,
+    // whether a line shorter than the first was seen, which has to be the last
+    last_line: bool,
+    regular: bool,
+}
+
+impl EntryScan {
+    fn new(keys: Vec<String>, offset: u64) -> Self {
+        EntryScan {
+            keys,
+            layout: SequenceLayout {
+                length: 0,
+                offset,
+                line_bases: 0,
+                line_width: 0,
+            },
+            last_line: false,
+            regular: true,
+        }
+    }
+
+    // Adds a sequence line with `bases` residues and `width` bytes.
+    fn push_line(&mut self, bases: u64, width: u64) {
+        let layout = &mut self.layout;
+        if bases == 0 {
+            // blank lines are only allowed at the end
+            self.last_line = true;
+            return;
+        }
+        if self.last_line {
+            self.regular = false;
+        } else if layout.line_bases == 0 {
+            layout.line_bases = bases;
+            layout.line_width = width;
+        } else if bases > layout.line_bases {
+            self.regular = false;
+        } else if width - bases != layout.line_width - layout.line_bases {
+            // only the last line may lack a line break
+            self.regular &= width == bases;
+            self.last_line = true;
+        } else if bases < layout.line_bases {
+            self.last_line = true;
+        }
+        layout.length += bases;
+    }
+
+    // Skips a `;` comment line of `width` bytes. Comments before the first
+    // sequence line move the sequence offset; like after blank lines, later
+    // sequence lines make the layout irregular.
+    fn push_comment(&mut self, width: u64) {
+        if self.layout.length == 0 && !self.last_line {
+            self.layout.offset += width;
+        } else {
+            self.last_line = true;
+        }
+    }
+
+    // Records the length and, if the lines are regular, the layout for all ids.
+    fn finish(self, index: &mut FastaIndex) {
+        for key in self.keys {
+            index
+                .id_to_length
+                .insert(key.clone(), self.layout.length as usize);
+            if self.regular {
+                index.id_to_layout.insert(key, self.layout);
+            } else {
+                // a replaced entry may have had one
+                index.id_to_layout.remove(&key);
+            }
+        }
+    }
+}
+
+/// The saved progress of an index build, see `FastaIndex::new_with_checkpoints`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct IndexCheckpoint {
+    /// The number of bytes scanned, always at the start of a description line.
+    pub offset: u64,
+    /// The entries found so far.
+    pub id_to_offset: HashMap<String, u64>,
+    pub separator: String,
+    pub id_index: usize,
+    /// The number of bytes between checkpoints.
+    pub interval: u64,
+    /// The size of the file, to detect changes.
+    pub file_len: u64,
+    /// The modification time of the file as seconds and nanoseconds since
+    /// the Unix epoch, if available, to detect changes.
+    pub modified: Option<(u64, u32)>,
+}
+
+impl IndexCheckpoint {
+    // Writes the checkpoint with the entries of `partial`, replacing the
+    // previous one only once it is complete.
+    fn save(&self, path: &Path, partial: &FastaIndex) -> Result<(), FastaError> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        // serialize a borrowing copy to avoid cloning the entries
+        serde_json::to_writer(
+            &mut file,
+            &CheckpointRef {
+                offset: self.offset,
+                id_to_offset: &partial.id_to_offset,
+                separator: &self.separator,
+                id_index: self.id_index,
+                interval: self.interval,
+                file_len: self.file_len,
+                modified: self.modified,
+            },
+        )
+        .map_err(Error::from)?;
+        file.flush()?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+// Serializes like `IndexCheckpoint`.
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    offset: u64,
+    id_to_offset: &'a HashMap<String, u64>,
+    separator: &'a str,
+    id_index: usize,
+    interval: u64,
+    file_len: u64,
+    modified: Option<(u64, u32)>,
+}
+
+// The size and modification time of a file.
+fn file_fingerprint(path: &Path) -> Result<(u64, Option<(u64, u32)>), FastaError> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| (d.as_secs(), d.subsec_nanos()));
+    Ok((metadata.len(), modified))
+}
+
+// A single line of a json lines index.
+#[derive(Serialize, Deserialize)]
+struct JsonlRecord {
+    id: String,
+    offset: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layout: Option<SequenceLayout>,
+}
+
+// Deserializes a json index, keeping only some ids.
+struct FilteredIndex<'a>(&'a HashSet<String>);
+
+impl<'de, 'a> DeserializeSeed<'de> for FilteredIndex<'a> {
+    type Value = FastaIndex;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for FilteredIndex<'a> {
+    type Value = FastaIndex;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a FastaIndex")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut res = FastaIndex {
+            id_to_offset: HashMap::new(),
+            id_to_layout: HashMap::new(),
+            id_to_length: HashMap::new(),
+        };
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "id_to_offset" {
+                res.id_to_offset = map.next_value_seed(FilteredEntries(self.0, PhantomData))?;
+            } else if key == "id_to_layout" {
+                res.id_to_layout = map.next_value_seed(FilteredEntries(self.0, PhantomData))?;
+            } else if key == "id_to_length" {
+                res.id_to_length = map.next_value_seed(FilteredEntries(self.0, PhantomData))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(res)
+    }
+}
+
+// Deserializes a map keyed by id, keeping only some ids.
+struct FilteredEntries<'a, T>(&'a HashSet<String>, PhantomData<T>);
+
+impl<'de, 'a, T: Deserialize<'de>> DeserializeSeed<'de> for FilteredEntries<'a, T> {
+    type Value = HashMap<String, T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, T: Deserialize<'de>> Visitor<'de> for FilteredEntries<'a, T> {
+    type Value = HashMap<String, T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of ids")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut res = HashMap::new();
+        while let Some(id) = map.next_key::<String>()? {
+            if self.0.contains(&id) {
+                res.insert(id, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(res)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::map::FastaMap;
-    use crate::pieces::FastaEntry;
+    use crate::pieces::{FastaEntry, FastaLengths};
+
+    #[test]
+    fn index_rejects_empty_ids() {
+        let path = std::env::temp_dir().join("fasta_rs_empty_ids.fasta");
+        std::fs::write(&path, ">a||x\nACGT\n>b||y\nGG\n").unwrap();
+        match FastaIndex::try_new(&path, "|", 1) {
+            Err(FastaError::Parse { kind, message }) => {
+                assert_eq!(kind, ErrorKind::EmptyId);
+                assert!(message.contains(">a||x"));
+            }
+            other => panic!("expected EmptyId error, got {:?}", other),
+        }
+        assert!(FastaIndex::try_new(&path, "|", 0).is_ok());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn index_keyed_by_regex() {
+        let path = Path::new("./resources/test.fasta");
+        let re = Regex::new(r"_(\w+) ").unwrap();
+        let index = FastaIndex::new_with_regex(path, &re).unwrap();
+        let by_separator = FastaIndex::new(path, "|", 1);
+        assert_eq!(
+            index.id_to_offset["PUSHI"],
+            by_separator.id_to_offset["Q2HZH0"]
+        );
+        assert_eq!(
+            index.id_to_offset["CAVPO"],
+            by_separator.id_to_offset["H0VS30"]
+        );
+
+        let re = Regex::new(r"OS=Homo (\w+)").unwrap();
+        match FastaIndex::new_with_regex(path, &re) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::NoIdMatch),
+            other => panic!("expected NoIdMatch error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn index_by_multiple_fields() {
+        let path = std::env::temp_dir().join("fasta_rs_multi_field.fasta");
+        std::fs::write(&path, ">sp|P1|A_HUMAN\nAC\n>sp|P2|B_HUMAN\nGG\n").unwrap();
+        let index = FastaIndex::new_multi_field(&path, &[("|", 1), ("|", 2)]).unwrap();
+        assert_eq!(index.id_to_offset.len(), 4);
+        assert_eq!(index.id_to_offset["P2"], index.id_to_offset["B_HUMAN"]);
+        assert_eq!(
+            FastaEntry::from_index(&path, index.id_to_offset["B_HUMAN"])
+                .unwrap()
+                .sequence,
+            "GG"
+        );
+
+        std::fs::write(&path, ">sp|P1|A_HUMAN\nAC\n>sp|A_HUMAN|B_HUMAN\nGG\n").unwrap();
+        match FastaIndex::new_multi_field(&path, &[("|", 1), ("|", 2)]) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::DuplicateId),
+            other => panic!("expected DuplicateId error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn duplicate_policies() {
+        let path = std::env::temp_dir().join("fasta_rs_duplicates.fasta");
+        std::fs::write(&path, ">a x\nAC\nGT\n>b\nG\n>a y\nA\nCGT\n").unwrap();
+        let with_lengths = |policy| {
+            FastaIndex::build(&path, policy, true, |line| {
+                Ok(vec![checked_seq_id(line, " ", 0)?])
+            })
+            .unwrap()
+        };
+        let first = with_lengths(DuplicatePolicy::FirstWins);
+        assert_eq!(first.id_to_offset["a"], 0);
+        assert_eq!(first.length("a"), Some(4));
+        assert!(first.base_offset("a", 2).is_some());
+        assert_eq!(
+            first.get_region(&path, "a", 0, 10).unwrap().sequence,
+            "ACGT"
+        );
+
+        let last = with_lengths(DuplicatePolicy::LastWins);
+        assert_eq!(last.id_to_offset["a"], 16);
+        assert_eq!(last.length("a"), Some(4));
+        // the last entry is wrapped irregularly, so the layout of the first is dropped
+        assert!(last.base_offset("a", 2).is_none());
+        assert_eq!(last.id_to_offset["b"], first.id_to_offset["b"]);
+        let plain = FastaIndex::new_with_policy(&path, " ", 0, DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(plain.id_to_offset, last.id_to_offset);
+        assert_eq!(plain.length("a"), None);
+
+        match FastaIndex::new_with_policy(&path, " ", 0, DuplicatePolicy::Error) {
+            Err(FastaError::Parse { kind, message }) => {
+                assert_eq!(kind, ErrorKind::DuplicateId);
+                assert!(message.contains("\"a\", at offsets 0 and 16"));
+            }
+            other => panic!("expected DuplicateId error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lengths_from_index() {
+        let path = Path::new("./resources/test_crlf.fasta");
+        assert!(FastaIndex::new(path, "|", 1).id_to_length.is_empty());
+        let index = FastaIndex::new_with_lengths(path, "|", 1).unwrap();
+        let expected = FastaLengths::from_fasta(Path::new("./resources/test.fasta"), "|", 1);
+        assert_eq!(index.id_to_length, expected.sequence_lengths);
+        assert_eq!(index.length("P93158"), Some(120));
+        assert_eq!(index.length("Q00000"), None);
+
+        // indices without lengths still load
+        let json = std::env::temp_dir().join("fasta_rs_lengths.index");
+        std::fs::write(&json, r#"{"id_to_offset":{"P93158":100}}"#).unwrap();
+        let old = FastaIndex::from_json(&json).unwrap();
+        assert_eq!(old.id_to_offset["P93158"], 100);
+        assert_eq!(old.length("P93158"), None);
+    }
+
+    #[test]
+    fn index_with_comment_lines() {
+        let path = Path::new("./resources/test_comments.fasta");
+        let index = FastaIndex::new_with_lengths(path, "|", 1).unwrap();
+        let plain =
+            FastaIndex::new_with_lengths(Path::new("./resources/test.fasta"), "|", 1).unwrap();
+        assert_eq!(index.id_to_length, plain.id_to_length);
+        // the first comment is before the sequence, the last one after it
+        assert_eq!(index.id_to_layout["Q2HZH0"].offset, 144);
+        assert!(!index.id_to_layout.contains_key("P93158"));
+        assert!(index.id_to_layout.contains_key("H0VS30"));
+
+        let ids: Vec<String> = plain.id_to_offset.keys().cloned().collect();
+        let map = FastaMap::from_index_with_ids(path, &index, &ids);
+        for id in &ids {
+            let entry = FastaEntry::from_index(path, index.id_to_offset[id]).unwrap();
+            assert_eq!(entry.sequence, map.id_to_seq[id]);
+            assert!(!entry.sequence.contains(';'));
+            assert_eq!(
+                index.get_region(path, id, 50, 70).unwrap(),
+                plain
+                    .get_region(Path::new("./resources/test.fasta"), id, 50, 70)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn index_resumed_from_checkpoint() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("fasta_rs_checkpoint.fasta");
+        let checkpoint = dir.join("fasta_rs_checkpoint.json");
+        std::fs::write(&path, ">a\nACGT\n>b\nGG\n>c\nTT\n>d\nAA\n").unwrap();
+        let expected = FastaIndex::new(&path, " ", 0);
+
+        let index = FastaIndex::new_with_checkpoints(&path, " ", 0, &checkpoint, 4).unwrap();
+        assert_eq!(index, expected);
+        assert!(!checkpoint.exists());
+
+        // as if interrupted after the entry of `b`
+        let done = |id: &String| expected.id_to_offset[id] < 14;
+        let partial = FastaIndex {
+            id_to_offset: expected
+                .id_to_offset
+                .iter()
+                .filter(|(id, _)| done(id))
+                .map(|(id, offset)| (id.clone(), *offset))
+                .collect(),
+            id_to_layout: HashMap::new(),
+            id_to_length: HashMap::new(),
+        };
+        let (file_len, modified) = file_fingerprint(&path).unwrap();
+        let state = IndexCheckpoint {
+            offset: 14,
+            id_to_offset: partial.id_to_offset.clone(),
+            separator: " ".to_string(),
+            id_index: 0,
+            interval: 4,
+            file_len,
+            modified,
+        };
+        assert_eq!(partial.id_to_offset.len(), 2);
+        state.save(&checkpoint, &partial).unwrap();
+        assert_eq!(
+            FastaIndex::resume_from(&checkpoint, &path).unwrap(),
+            expected
+        );
+        assert!(!checkpoint.exists());
+
+        state.save(&checkpoint, &partial).unwrap();
+        std::fs::write(&path, ">a\nACGT\n>b\nGG\n>c\nTT\n>d\nAA\n>e\nCC\n").unwrap();
+        match FastaIndex::resume_from(&checkpoint, &path) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::FileChanged),
+            other => panic!("expected FileChanged error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn regions_from_fai() {
+        let fasta = std::env::temp_dir().join("fasta_rs_faidx.fasta");
+        let fai = std::env::temp_dir().join("fasta_rs_faidx.fasta.fai");
+        std::fs::write(
+            &fasta,
+            ">chr1 first\r\nACGTA\r\nCCGGT\r\nTT\r\n>chr2 second\r\nGATTACAGAT\r\n",
+        )
+        .unwrap();
+        std::fs::write(&fai, "chr1\t12\t13\t5\t7\nchr2\t10\t45\t10\t12\n").unwrap();
+        let faidx = FastaIndex::from_fai(&fai).unwrap();
+        #[allow(deprecated)]
+        let alias = FastaIndex::from_faidx(&fai).unwrap();
+        assert_eq!(alias, faidx);
+        let index = FastaIndex::new(&fasta, " ", 0);
+        for (id, start, end) in [
+            ("chr1", 0, 5),
+            ("chr1", 3, 11),
+            ("chr1", 4, 100),
+            ("chr1", 12, 20),
+            ("chr2", 2, 7),
+        ] {
+            assert_eq!(
+                faidx.get_region(&fasta, id, start, end).unwrap(),
+                index.get_region(&fasta, id, start, end).unwrap()
+            );
+        }
+        assert_eq!(
+            faidx.get_region(&fasta, "chr1", 3, 11).unwrap().sequence,
+            "TACCGGTT"
+        );
+        let bytes = std::fs::read(&fasta).unwrap();
+        for base in 0..12 {
+            let offset = faidx.base_offset("chr1", base).unwrap() as usize;
+            assert_eq!(bytes[offset], b"ACGTACCGGTTT"[base]);
+        }
+        assert_eq!(faidx.base_offset("chr1", 5), Some(20));
+        assert_eq!(faidx.base_offset("chr2", 9), Some(54));
+        assert_eq!(faidx.base_offset("chr1", 12), None);
+        assert_eq!(faidx.base_offset("chr3", 0), None);
+
+        std::fs::write(&fai, "chr1\t12\t13\t0\t7\n").unwrap();
+        match FastaIndex::from_fai(&fai) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::InvalidIndex),
+            other => panic!("expected InvalidIndex error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fai_from_index() {
+        let fasta = std::env::temp_dir().join("fasta_rs_to_fai.fasta");
+        let fai = std::env::temp_dir().join("fasta_rs_to_fai.fasta.fai");
+        // the layouts samtools writes for this file
+        let expected = "chr1\t12\t13\t5\t7\nchr2\t10\t45\t10\t12\n";
+        std::fs::write(
+            &fasta,
+            ">chr1 first\r\nACGTA\r\nCCGGT\r\nTT\r\n>chr2 second\r\nGATTACAGAT\r\n",
+        )
+        .unwrap();
+        let index = FastaIndex::new(&fasta, " ", 0);
+        match index.to_fai(&fai).unwrap_err().into_inner() {
+            Some(e) => assert!(e.to_string().contains("new_with_lengths")),
+            None => panic!("expected an error message"),
+        }
+        let index = FastaIndex::new_with_lengths(&fasta, " ", 0).unwrap();
+        index.to_fai(&fai).unwrap();
+        assert_eq!(read_to_string(&fai).unwrap(), expected);
+        let loaded = FastaIndex::from_fai(&fai).unwrap();
+        assert_eq!(loaded.id_to_layout, index.id_to_layout);
+        loaded.to_fai(&fai).unwrap();
+        assert_eq!(read_to_string(&fai).unwrap(), expected);
+
+        // empty sequences and a last line without line break
+        std::fs::write(&fasta, ">a\n>b\nACG\nAC").unwrap();
+        FastaIndex::new_with_lengths(&fasta, " ", 0)
+            .unwrap()
+            .to_fai(&fai)
+            .unwrap();
+        assert_eq!(
+            read_to_string(&fai).unwrap(),
+            "a\t0\t3\t0\t0\nb\t5\t6\t3\t4\n"
+        );
+        let loaded = FastaIndex::from_fai(&fai).unwrap();
+        assert_eq!(loaded.get_region(&fasta, "a", 0, 5).unwrap().sequence, "");
+
+        // irregular lines are only read line by line
+        for irregular in [">a\nACG\nACGT\n", ">a\nAC\nAC\r\nA\n", ">a\nAC\n\nAC\n"] {
+            std::fs::write(&fasta, irregular).unwrap();
+            let index = FastaIndex::new_with_lengths(&fasta, " ", 0).unwrap();
+            assert!(index.id_to_layout.is_empty());
+            assert!(index.length("a").is_some());
+            let err = index.to_fai(&fai).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn index_on_compressed_file() {
+        let gz = Path::new("./resources/test.fasta.gz");
+        match FastaIndex::try_new(gz, "|", 1) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::SeekOnCompressed),
+            other => panic!("expected SeekOnCompressed error, got {:?}", other),
+        }
+        let index = FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1);
+        match FastaMap::try_from_index_with_ids(gz, &index, &["P93158".to_string()]) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::SeekOnCompressed),
+            other => panic!("expected SeekOnCompressed error, got {:?}", other),
+        }
+        let err = FastaHandle::open_fasta(gz)
+            .seek(SeekFrom::Start(0))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert!(FastaIndex::try_new(Path::new("./resources/missing.fasta"), "|", 1).is_err());
+    }
 
     #[test]
     fn index_building() {
@@ -131,4 +1451,148 @@ mod tests {
         };
         assert_eq!(exp_entry, entry);
     }
+
+    #[test]
+    fn crlf_entries_from_index() {
+        let lf = Path::new("./resources/test.fasta");
+        let crlf = Path::new("./resources/test_crlf.fasta");
+        let index = FastaIndex::new(crlf, "|", 1);
+        let lf_index = FastaIndex::new(lf, "|", 1);
+        let mut ids = index.id_to_offset.keys().collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec!["H0VS30", "P93158", "Q2HZH0"]);
+        for (id, offset) in &index.id_to_offset {
+            let entry = FastaEntry::from_index(crlf, *offset).unwrap();
+            assert!(!entry.description.contains('\r'));
+            assert!(!entry.sequence.contains('\r'));
+            assert_eq!(
+                entry,
+                FastaEntry::from_index(lf, lf_index.id_to_offset[id]).unwrap()
+            );
+        }
+        let ids = vec!["P93158".to_string()];
+        let map = FastaMap::from_index_with_ids(crlf, &index, &ids);
+        assert!(!map.id_to_seq["P93158"].contains('\r'));
+        for record in crate::read::FastaReader::new(crlf) {
+            let [description, seq] = record.unwrap();
+            assert!(!description.contains('\r') && !seq.contains('\r'));
+        }
+    }
+
+    #[test]
+    fn stranded_regions() {
+        let path = std::env::temp_dir().join("fasta_rs_stranded_regions.fasta");
+        std::fs::write(&path, ">chr1\nAACCGGTTac\ngtNNRYaa\n").unwrap();
+        let index = FastaIndex::new(&path, "|", 1);
+
+        let plus = index
+            .get_region_stranded(&path, "chr1", 6, 13, Strand::Plus)
+            .unwrap();
+        assert_eq!(plus.description, ">chr1:7-13(+)");
+        assert_eq!(plus.sequence, "TTacgtN");
+        let minus = index
+            .get_region_stranded(&path, "chr1", 6, 13, Strand::Minus)
+            .unwrap();
+        assert_eq!(minus.description, ">chr1:7-13(-)");
+        assert_eq!(minus.sequence, "NacgtAA");
+    }
+
+    #[test]
+    fn index_without_trailing_newline() {
+        let index = FastaIndex::new(Path::new("./resources/test_no_newline.fasta"), "|", 1);
+        assert_eq!(index.id_to_offset.len(), 2);
+        assert_eq!(index.id_to_offset["Q2HZH0"], 0);
+        assert_eq!(index.id_to_offset["P93158"], 130);
+
+        let path = Path::new("./resources/test_no_newline_seq.fasta");
+        let index = FastaIndex::new(path, "|", 1);
+        assert_eq!(
+            index.get_region(path, "Q2HZH0", 115, 200).unwrap().sequence,
+            "VCDAP"
+        );
+        let entries = crate::read::FastaReader::new(path)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0][1].ends_with("VCDAP"));
+    }
+
+    #[test]
+    fn partial_index_loading() {
+        let ids = ["H0VS30", "Q2HZH0", "XXXXXX"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<HashSet<_>>();
+        let index = FastaIndex::load_ids(Path::new("./resources/test.index"), &ids).unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("H0VS30".to_string(), 422);
+        expected.insert("Q2HZH0".to_string(), 0);
+        assert_eq!(index.id_to_offset, expected);
+    }
+
+    #[test]
+    fn jsonl_index() {
+        let index = FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1);
+        let outpath = std::env::temp_dir().join("fasta_rs_index.jsonl");
+        index.to_jsonl(&outpath).unwrap();
+        assert_eq!(
+            read_to_string(&outpath).unwrap(),
+            "{\"id\":\"Q2HZH0\",\"offset\":0}\n\
+             {\"id\":\"P93158\",\"offset\":206}\n\
+             {\"id\":\"H0VS30\",\"offset\":422}\n"
+        );
+        assert_eq!(FastaIndex::from_jsonl(&outpath).unwrap(), index);
+
+        let index =
+            FastaIndex::new_with_lengths(Path::new("./resources/test.fasta"), "|", 1).unwrap();
+        index.to_jsonl(&outpath).unwrap();
+        assert_eq!(
+            read_to_string(&outpath).unwrap().lines().next().unwrap(),
+            "{\"id\":\"Q2HZH0\",\"offset\":0,\"length\":120,\
+             \"layout\":{\"length\":120,\"offset\":83,\"line_bases\":60,\"line_width\":61}}"
+        );
+        assert_eq!(FastaIndex::from_jsonl(&outpath).unwrap(), index);
+
+        let ids = vec!["P93158".to_string()].into_iter().collect();
+        let partial = FastaIndex::load_ids_jsonl(&outpath, &ids).unwrap();
+        assert_eq!(partial.id_to_offset.len(), 1);
+        assert_eq!(partial.id_to_offset["P93158"], 206);
+    }
+
+    #[test]
+    fn record_containing_offset() {
+        let index = FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1);
+        assert_eq!(index.record_at_offset(0).unwrap(), "Q2HZH0");
+        assert_eq!(index.record_at_offset(205).unwrap(), "Q2HZH0");
+        assert_eq!(index.record_at_offset(206).unwrap(), "P93158");
+        assert_eq!(index.record_at_offset(500).unwrap(), "H0VS30");
+
+        let mut index = index;
+        index.id_to_offset.remove("Q2HZH0");
+        assert_eq!(index.record_at_offset(100), None);
+    }
+
+    #[test]
+    fn flanks_from_index() {
+        let path = Path::new("./resources/test.fasta");
+        let index = FastaIndex::new(path, "|", 1);
+
+        // window spanning a line break
+        let flank = index.get_flank(path, "Q2HZH0", 60, 2).unwrap();
+        assert_eq!(flank.description, ">Q2HZH0:59-63");
+        assert_eq!(flank.sequence, "QNKSL");
+
+        // clamped at the sequence end
+        let flank = index.get_flank(path, "H0VS30", 178, 10).unwrap();
+        assert_eq!(flank.description, ">H0VS30:169-180");
+        assert_eq!(flank.sequence, "SEGTTLKDLIYD");
+
+        assert!(matches!(
+            index.get_flank(path, "XXXXXX", 10, 2),
+            Err(FastaError::Parse {
+                kind: ErrorKind::IdNotFound,
+                ..
+            })
+        ));
+    }
 }
@@ -0,0 +1,149 @@
+//! Random access to the entries of an indexed FASTA file.
+
+use crate::errors::{ErrorKind, FastaError};
+use crate::helpers::{offset_past_eof_error, open_detected, seek_on_compressed_error, Compression};
+use crate::index::FastaIndex;
+use crate::pieces::FastaEntry;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek};
+use std::path::Path;
+
+/// A reader that keeps an indexed FASTA file open for repeated lookups by id.
+///
+/// The file handle and its read buffer are reused across lookups, so
+/// entries that are close to each other in the file are read without
+/// rereading the buffer. Only uncompressed files can be read this way.
+///
+/// # Examples
+/// ```
+/// use fasta::index::FastaIndex;
+/// use fasta::indexed::IndexedReader;
+/// use std::path::Path;
+///
+/// let path = Path::new("./resources/test.fasta");
+/// let mut reader = IndexedReader::new(path, FastaIndex::new(path, "|", 1)).unwrap();
+/// let entry = reader.get("P93158").unwrap().unwrap();
+/// assert!(entry.description.starts_with(">tr|P93158|"));
+/// assert!(reader.get("Q00000").unwrap().is_none());
+/// ```
+pub struct IndexedReader {
+    reader: BufReader<File>,
+    index: FastaIndex,
+    line_buf: String,
+}
+
+impl IndexedReader {
+    /// Opens `path` for lookups with `index`, which has to be built from
+    /// the same file. Compressed files are an error, as they can't be read
+    /// from an offset.
+    pub fn new(path: &Path, index: FastaIndex) -> Result<Self, FastaError> {
+        let (compression, reader) = open_detected(path)?;
+        if compression != Compression::Plain {
+            return Err(seek_on_compressed_error(path));
+        }
+        Ok(IndexedReader {
+            reader,
+            index,
+            line_buf: String::new(),
+        })
+    }
+
+    /// The index used for lookups.
+    pub fn index(&self) -> &FastaIndex {
+        &self.index
+    }
+
+    /// Reads the entry `id`, or returns `None` if it is not in the index.
+    ///
+    /// The sequence ends at the next description line, blank line or the
+    /// end of the file, like in `FastaEntry::from_index`.
+    pub fn get(&mut self, id: &str) -> Result<Option<FastaEntry>, FastaError> {
+        let offset = match self.index.id_to_offset.get(id) {
+            Some(offset) => *offset,
+            None => return Ok(None),
+        };
+        // seeking relative to the current position keeps the buffer if possible
+        let position = self.reader.stream_position()?;
+        self.reader.seek_relative(offset as i64 - position as i64)?;
+
+        let description = match self.next_line()? {
+            Some(line) if line.starts_with('>') => line.to_string(),
+            Some(_) => {
+                return Err(FastaError::parse(
+                    ErrorKind::IndexNotAtDescription,
+                    format!(
+                        "No description line found at offset {} for {:?}.",
+                        offset, id
+                    ),
+                ))
+            }
+            None => return Err(offset_past_eof_error(offset)),
+        };
+        let mut sequence = String::new();
+        while let Some(line) = self.next_line()? {
+            if line.is_empty() || line.starts_with('>') {
+                break;
+            }
+            if !line.starts_with(';') {
+                sequence.push_str(line);
+            }
+        }
+        Ok(Some(FastaEntry {
+            description,
+            sequence,
+        }))
+    }
+
+    // Reads the next line without its line break, or `None` at the end of the file.
+    fn next_line(&mut self) -> io::Result<Option<&str>> {
+        self.line_buf.clear();
+        if self.reader.read_line(&mut self.line_buf)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.line_buf.trim_end_matches(&['\n', '\r'][..])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::read_all;
+
+    #[test]
+    fn repeated_lookups() {
+        let path = Path::new("./resources/test_crlf.fasta");
+        let index = FastaIndex::new(path, "|", 1);
+        let mut reader = IndexedReader::new(path, index).unwrap();
+        let entries = read_all(Path::new("./resources/test.fasta")).unwrap();
+        // backwards, forwards and repeated
+        for (id, i) in [("H0VS30", 2), ("Q2HZH0", 0), ("P93158", 1), ("P93158", 1)] {
+            assert_eq!(reader.get(id).unwrap().unwrap(), entries[i]);
+        }
+        assert!(reader.get("Q00000").unwrap().is_none());
+        assert!(IndexedReader::new(
+            Path::new("./resources/test_gzip_misnamed.fasta"),
+            FastaIndex::new(path, "|", 1)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn stale_offsets() {
+        let path = Path::new("./resources/test.fasta");
+        let mut index = FastaIndex::new(path, "|", 1);
+        index.id_to_offset.insert("past".to_string(), 100_000);
+        index.id_to_offset.insert("inside".to_string(), 3);
+        let mut reader = IndexedReader::new(path, index).unwrap();
+        match reader.get("past") {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::OffsetPastEof),
+            other => panic!("expected OffsetPastEof error, got {:?}", other),
+        }
+        match reader.get("inside") {
+            Err(FastaError::Parse { kind, .. }) => {
+                assert_eq!(kind, ErrorKind::IndexNotAtDescription)
+            }
+            other => panic!("expected IndexNotAtDescription error, got {:?}", other),
+        }
+    }
+}
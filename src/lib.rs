@@ -6,6 +6,10 @@
 pub mod errors;
 pub mod helpers;
 pub mod index;
+pub mod indexed;
 pub mod map;
 pub mod pieces;
 pub mod read;
+pub mod transform;
+pub mod translate;
+pub mod write;
@@ -1,11 +1,14 @@
 //! A HashMap representation of a FASTA file.
 
+use crate::errors::{ErrorKind, FastaError};
+use crate::helpers::{empty_id_error, offset_past_eof_error, seek_on_compressed_error};
 use crate::index::FastaIndex;
 use crate::read::{FastaHandle, FastaReader};
+use crate::write::{FastaWriter, WriteOptions};
 
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
 use std::path::Path;
 
 /// A HashMap representation of a Fasta file.
@@ -16,65 +19,191 @@ pub struct FastaMap {
 
 impl FastaMap {
     pub fn from_fasta(path: &Path) -> Self {
-        let reader = FastaReader::new(path);
+        Self::try_from_fasta(path).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `from_fasta`, but returns an error instead of panicking,
+    /// e.g. if a description line is empty.
+    pub fn try_from_fasta(path: &Path) -> Result<Self, FastaError> {
+        let mut reader = FastaReader::try_new(path)?;
         let mut entries: HashMap<String, String> = HashMap::new();
-        for [header, seq] in reader {
+        while let Some(record) = reader.read_record() {
+            let [header, seq] = record?;
+            if header[1..].trim().is_empty() {
+                return Err(empty_id_error(&header));
+            }
             entries.insert(header, seq);
         }
-        FastaMap { id_to_seq: entries }
+        Ok(FastaMap { id_to_seq: entries })
     }
 
     pub fn from_index_with_ids(path: &Path, index: &FastaIndex, ids: &[String]) -> Self {
+        Self::try_from_index_with_ids(path, index, ids).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `from_index_with_ids`, but returns an error instead of panicking,
+    /// e.g. if an offset of the index points past the end of the file or
+    /// `path` is compressed.
+    pub fn try_from_index_with_ids(
+        path: &Path,
+        index: &FastaIndex,
+        ids: &[String],
+    ) -> Result<Self, FastaError> {
         let mut res = HashMap::new();
-        let mut fasta_handle = FastaHandle::open_fasta(path);
-        if let FastaHandle::Compressed(_) = fasta_handle {
-            panic!(
-                "Tried to use index on non seekable compressed file: {:?}",
-                path
-            );
+        let mut fasta_handle = FastaHandle::try_open_fasta(path)?;
+        if !matches!(fasta_handle, FastaHandle::Uncompressed(_)) {
+            return Err(seek_on_compressed_error(path));
         }
 
-        for k in ids {
-            if let Some(v) = index.id_to_offset.get(k) {
-                let mut seq_buf = String::new();
-                fasta_handle
-                    .seek(SeekFrom::Start(*v))
-                    .expect("File seek failed in `from_index_with_ids`.");
-
-                let mut seen_header = false;
-                for line in BufReader::new(&mut fasta_handle).lines() {
-                    let lstring = line.unwrap();
-                    if lstring.starts_with('>') {
-                        if seen_header {
-                            break;
-                        } else {
-                            seen_header = true;
-                        }
-                    } else if lstring == "" {
+        // read in file order to avoid seeking back and forth
+        let mut offsets = ids
+            .iter()
+            .filter_map(|k| index.id_to_offset.get(k).map(|v| (k, v)))
+            .collect::<Vec<_>>();
+        offsets.sort_unstable_by_key(|(_, v)| **v);
+        for (k, v) in offsets {
+            let mut seq_buf = String::new();
+            fasta_handle.seek(SeekFrom::Start(*v))?;
+
+            let mut seen_header = false;
+            let mut past_eof = true;
+            for line in BufReader::new(&mut fasta_handle).lines() {
+                let mut lstring = line?;
+                past_eof = false;
+                if lstring.ends_with('\r') {
+                    lstring.pop();
+                }
+                if lstring.starts_with('>') {
+                    if seen_header {
                         break;
                     } else {
-                        seq_buf.push_str(&lstring);
+                        seen_header = true;
                     }
+                } else if lstring.is_empty() {
+                    break;
+                } else if !lstring.starts_with(';') {
+                    seq_buf.push_str(&lstring);
                 }
-                res.insert((*k).to_string(), seq_buf);
             }
+            if past_eof {
+                return Err(offset_past_eof_error(*v));
+            }
+            res.insert((*k).to_string(), seq_buf);
         }
-        FastaMap { id_to_seq: res }
+        Ok(FastaMap { id_to_seq: res })
     }
 
-    pub fn to_fasta(&self, path: &Path) {
-        let mut f = match File::create(path) {
-            Err(why) => panic!("couldn't create {:?}: {:?}", path, why),
-            Ok(file) => BufWriter::new(file),
+    /// Reads the residues `start..=end` (1-based, inclusive, like
+    /// `samtools faidx id:start-end`) of the entry `id` of an indexed file.
+    ///
+    /// Only the lines covering the range are read, see `FastaIndex::get_region`.
+    /// It is an error if `id` is not in the index, if `start` is 0 or
+    /// greater than `end`, or if `end` exceeds the sequence.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::index::FastaIndex;
+    /// use fasta::map::FastaMap;
+    /// use std::path::Path;
+    ///
+    /// let path = Path::new("./resources/test.fasta");
+    /// let index = FastaIndex::new(path, "|", 1);
+    /// assert_eq!(FastaMap::subsequence(path, &index, "P93158", 1, 4).unwrap(), "TLKV");
+    /// assert!(FastaMap::subsequence(path, &index, "P93158", 100, 200).is_err());
+    /// ```
+    pub fn subsequence(
+        path: &Path,
+        index: &FastaIndex,
+        id: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<String, FastaError> {
+        let invalid_range = |reason: &str| {
+            FastaError::parse(
+                ErrorKind::InvalidRange,
+                format!("Invalid range {}-{} for {:?}: {}", start, end, id, reason),
+            )
         };
+        if start == 0 || start > end {
+            return Err(invalid_range("expected 1 <= start <= end"));
+        }
+        let region = index.get_region(path, id, start - 1, end)?;
+        if region.sequence.len() < end - start + 1 {
+            return Err(invalid_range("end exceeds the sequence"));
+        }
+        Ok(region.sequence)
+    }
+
+    /// Writes the entries with each sequence on a single line.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::map::FastaMap;
+    /// use std::path::Path;
+    ///
+    /// let map = FastaMap::from_fasta(Path::new("./resources/test.fasta"));
+    /// let outpath = std::env::temp_dir().join("fasta_map_doctest.fasta");
+    /// map.to_fasta(&outpath).expect("Writing FASTA failed");
+    /// ```
+    pub fn to_fasta(&self, path: &Path) -> io::Result<()> {
+        self.to_fasta_wrapped(path, 0)
+    }
+
+    /// Writes the entries with sequences wrapped at `width` residues per line;
+    /// a width of 0 writes each sequence on a single line.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::map::FastaMap;
+    /// use std::path::Path;
+    ///
+    /// let map = FastaMap::from_fasta(Path::new("./resources/test.fasta"));
+    /// let outpath = std::env::temp_dir().join("fasta_map_wrapped_doctest.fasta");
+    /// map.to_fasta_wrapped(&outpath, 80).unwrap();
+    /// ```
+    pub fn to_fasta_wrapped(&self, path: &Path, width: usize) -> io::Result<()> {
+        self.to_fasta_with_options(
+            path,
+            WriteOptions {
+                width,
+                ..WriteOptions::default()
+            },
+        )
+    }
+
+    /// Writes the entries formatted according to `options`, e.g. with a blank
+    /// line between entries.
+    pub fn to_fasta_with_options(&self, path: &Path, options: WriteOptions) -> io::Result<()> {
+        let mut writer = FastaWriter::with_options(path, options)?;
         for (k, v) in self.id_to_seq.iter() {
-            if let Err(why) = f.write_all(format!(">{}\n", k).as_bytes()) {
-                panic!("couldn't write to {:?}: {:?}", path, why)
-            };
-            if let Err(why) = f.write_all(format!("{}\n\n", v).as_bytes()) {
-                panic!("couldn't write to {:?}: {:?}", path, why)
-            };
+            writer.write_entry(k, v)?;
+        }
+        writer.finish()
+    }
+
+    /// Writes one `id<TAB>sequence` line per entry, sorted by id.
+    ///
+    /// The id is the description up to the first whitespace, without the
+    /// leading `>`, so the rest of the description can't break the table.
+    pub fn to_tsv(&self, path: &Path) -> io::Result<()> {
+        let mut f = BufWriter::new(File::create(path)?);
+        let mut entries = self
+            .id_to_seq
+            .iter()
+            .map(|(description, seq)| {
+                let id = description
+                    .trim_start_matches('>')
+                    .split(char::is_whitespace)
+                    .next()
+                    .unwrap_or("");
+                (id, seq)
+            })
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        for (id, seq) in entries {
+            writeln!(f, "{}\t{}", id, seq)?;
         }
+        f.flush()
     }
 }
 
@@ -82,6 +211,94 @@ impl FastaMap {
 mod tests {
     use super::*;
 
+    #[test]
+    fn wrapped_map_round_trip() {
+        let map = FastaMap::from_fasta(Path::new("./resources/test.fasta"));
+        let outpath = std::env::temp_dir().join("fasta_rs_map_wrapped.fasta");
+        map.to_fasta_wrapped(&outpath, 50).unwrap();
+        let written = std::fs::read_to_string(&outpath).unwrap();
+        assert!(written.lines().all(|l| l.len() <= 50 || l.starts_with('>')));
+        assert!(!written.contains("\n\n") && !written.contains(">>"));
+        assert_eq!(FastaMap::from_fasta(&outpath), map);
+
+        map.to_fasta(&outpath).unwrap();
+        let written = std::fs::read_to_string(&outpath).unwrap();
+        assert_eq!(written.lines().count(), 6);
+        assert_eq!(FastaMap::from_fasta(&outpath), map);
+    }
+
+    #[test]
+    fn write_errors_are_returned() {
+        let map = FastaMap::from_fasta(Path::new("./resources/test.fasta"));
+        let outpath = Path::new("./resources/does_not_exist/out.fasta");
+        assert_eq!(
+            map.to_fasta(outpath).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn index_lookup_independent_of_id_order() {
+        let path = Path::new("./resources/test.fasta");
+        let index = FastaIndex::new(path, "|", 1);
+        let mut ids = ["H0VS30", "Q00000", "Q2HZH0", "P93158"].map(String::from);
+        let expected = FastaMap::from_index_with_ids(path, &index, &ids);
+        assert_eq!(expected.id_to_seq.len(), 3);
+        ids.reverse();
+        assert_eq!(FastaMap::from_index_with_ids(path, &index, &ids), expected);
+        ids.swap(0, 2);
+        assert_eq!(FastaMap::from_index_with_ids(path, &index, &ids), expected);
+    }
+
+    #[test]
+    fn subsequences_by_coordinates() {
+        let path = Path::new("./resources/test_crlf.fasta");
+        let index = FastaIndex::new(path, "|", 1);
+        // the first line holds 60 residues
+        assert_eq!(
+            FastaMap::subsequence(path, &index, "P93158", 58, 63).unwrap(),
+            "EDLLKC"
+        );
+        assert_eq!(
+            FastaMap::subsequence(path, &index, "P93158", 120, 120).unwrap(),
+            "V"
+        );
+        for (id, start, end) in [("P93158", 0, 5), ("P93158", 6, 5), ("P93158", 100, 121)] {
+            match FastaMap::subsequence(path, &index, id, start, end) {
+                Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::InvalidRange),
+                other => panic!("expected InvalidRange error, got {:?}", other),
+            }
+        }
+        match FastaMap::subsequence(path, &index, "Q00000", 1, 2) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::IdNotFound),
+            other => panic!("expected IdNotFound error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn offset_past_eof_is_an_error() {
+        let path = Path::new("./resources/test.fasta");
+        let mut index = FastaIndex::new(path, "|", 1);
+        let file_len = std::fs::metadata(path).unwrap().len();
+        index.id_to_offset.insert("Q2HZH0".to_string(), file_len);
+        let ids = vec!["Q2HZH0".to_string()];
+        match FastaMap::try_from_index_with_ids(path, &index, &ids) {
+            Err(FastaError::Parse {
+                kind: ErrorKind::OffsetPastEof,
+                ..
+            }) => (),
+            other => panic!("expected OffsetPastEof error, got {:?}", other),
+        }
+        let err = crate::pieces::FastaEntry::from_index(path, file_len).unwrap_err();
+        match err.downcast_ref::<FastaError>() {
+            Some(FastaError::Parse {
+                kind: ErrorKind::OffsetPastEof,
+                ..
+            }) => (),
+            other => panic!("expected OffsetPastEof error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn fasta_map_from_fasta() {
         let mut exp_map = HashMap::new();
@@ -112,4 +329,14 @@ mod tests {
             FastaMap::from_fasta(Path::new("./resources/test_short_descr.fasta"))
         );
     }
+
+    #[test]
+    fn fasta_map_to_tsv() {
+        let mut id_to_seq = HashMap::new();
+        id_to_seq.insert(">b\tsecond entry".to_string(), "GG".to_string());
+        id_to_seq.insert(">a".to_string(), "ACGT".to_string());
+        let path = std::env::temp_dir().join("fasta_rs_map.tsv");
+        FastaMap { id_to_seq }.to_tsv(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\tACGT\nb\tGG\n");
+    }
 }
@@ -1,17 +1,51 @@
 //! Utilities for reading whole FASTA files into iterators.
 
-use crate::helpers::open;
+use crate::errors::{ErrorKind, FastaError};
+use crate::helpers::{
+    checked_seq_id, compression_of, decoder, decompress, file_compression, gc_content, gc_counts,
+    open_detected, scan_descriptions, seq_id_from_description, seq_id_range, sequence_hash,
+    try_open, Alphabet, Compression,
+};
+use crate::index::{FastaIndex, SequenceLayout};
+use crate::pieces::{FastaEntry, PadAlign};
+use crate::transform::{SeqTransform, TransformPipeline};
+use crate::write::{FastaWriter, WriteOptions, DEFAULT_WIDTH};
 use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "regex")]
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io;
 use std::io::prelude::Seek;
-use std::io::{BufRead, BufReader, Read, SeekFrom};
-use std::path::Path;
+use std::io::{BufRead, BufReader, BufWriter, Read, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
 
-/// An enum that wraps compressed (gz) and uncompressed files.
-#[derive(Debug)]
+/// An enum that wraps compressed (gz, zstd) and uncompressed files.
+#[non_exhaustive]
 pub enum FastaHandle {
     Compressed(MultiGzDecoder<BufReader<File>>),
     Uncompressed(BufReader<File>),
+    /// A zstd compressed file, only with the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Decoder<'static, BufReader<File>>),
+}
+
+impl std::fmt::Debug for FastaHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FastaHandle::Compressed(s) => f.debug_tuple("Compressed").field(s).finish(),
+            FastaHandle::Uncompressed(s) => f.debug_tuple("Uncompressed").field(s).finish(),
+            // the zstd decoder does not implement `Debug`
+            #[cfg(feature = "zstd")]
+            FastaHandle::Zstd(s) => f.debug_tuple("Zstd").field(s.get_ref()).finish(),
+        }
+    }
 }
 
 impl Read for FastaHandle {
@@ -19,6 +53,8 @@ impl Read for FastaHandle {
         match self {
             FastaHandle::Compressed(s) => s.read(buf),
             FastaHandle::Uncompressed(s) => s.read(buf),
+            #[cfg(feature = "zstd")]
+            FastaHandle::Zstd(s) => s.read(buf),
         }
     }
 }
@@ -26,37 +62,51 @@ impl Read for FastaHandle {
 impl Seek for FastaHandle {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         match self {
-            FastaHandle::Compressed(_s) => panic!("Cannot seek in gzipped file!"),
             FastaHandle::Uncompressed(s) => s.seek(pos),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Cannot seek in compressed file",
+            )),
         }
     }
 }
 
 impl FastaHandle {
+    /// Opens `path`, decompressing it if it starts with the gzip or zstd
+    /// magic bytes, whatever its extension.
     pub fn open_fasta(path: &Path) -> FastaHandle {
-        if let Some(extension) = path.extension() {
-            match extension.to_str().unwrap() {
-                "gz" => {
-                    let fin = File::open(path)
-                        .unwrap_or_else(|_| panic!("Could not open path: {}", path.display()));
-                    FastaHandle::Compressed(MultiGzDecoder::new(BufReader::new(fin)))
-                }
-                _ => FastaHandle::Uncompressed(BufReader::new(
-                    File::open(path)
-                        .unwrap_or_else(|_| panic!("Could not open path: {}", path.display())),
-                )),
-            }
-        } else {
-            FastaHandle::Uncompressed(BufReader::new(
-                File::open(path)
-                    .unwrap_or_else(|_| panic!("Could not open path: {}", path.display())),
-            ))
+        Self::try_open_fasta(path)
+            .unwrap_or_else(|e| panic!("Could not open path {}: {}", path.display(), e))
+    }
+
+    /// Like `open_fasta`, but returns an error instead of panicking.
+    pub fn try_open_fasta(path: &Path) -> io::Result<FastaHandle> {
+        let (compression, fin) = open_detected(path)?;
+        match compression {
+            Compression::Plain => Ok(FastaHandle::Uncompressed(fin)),
+            Compression::Gzip => Ok(FastaHandle::Compressed(MultiGzDecoder::new(fin))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(FastaHandle::Zstd(zstd::Decoder::with_buffer(fin)?)),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(crate::helpers::zstd_unsupported()),
         }
     }
 }
 
 /// A reader that visits entries in a FASTA file one by one.
 ///
+/// Entries are yielded as `Ok([description, sequence])`; read errors, like
+/// a corrupt gzip stream or invalid UTF-8, are yielded as `Err`.
+///
+/// An empty file, or one with only blank lines, has no entries.
+/// A description without sequence lines is an entry with an empty sequence.
+/// Lines starting with `;` are comments, as in the original Pearson format,
+/// and are skipped; see `with_comments` to keep them.
+/// Content other than blank lines and comments before the first
+/// description is an error, unless the reader is lenient. The collections built from files
+/// (`FastaIndex`, `FastaMap`, `FastaAccessions`, `FastaLengths`)
+/// follow the same rules.
+///
 /// # Examples
 ///
 /// Iterate through a FASTA file:
@@ -65,62 +115,3518 @@ impl FastaHandle {
 /// use std::path::Path;
 ///
 /// let infile = Path::new("./resources/test.fasta");
-/// for [description, seq] in FastaReader::new(infile) {
+/// for record in FastaReader::new(infile) {
+///     let [description, seq] = record.expect("Reading failed");
 ///     println!("{:?}", description);
 ///     println!("{:?}", seq);
 /// }
 /// ```
 pub struct FastaReader {
-    lines: std::io::Lines<std::io::BufReader<std::boxed::Box<dyn std::io::Read>>>,
+    // `None` once the end of the input is reached, which closes the file
+    input: Option<BufReader<Box<dyn Read>>>,
+    // raw bytes of the last line read
+    line_buf: Vec<u8>,
     description: Option<String>,
     seq_buf: String,
+    // raw bytes of the current record; sequence lines only if `keep_raw`
+    raw_buf: Vec<u8>,
+    // raw bytes of the record returned last
+    raw_record: Vec<u8>,
+    keep_raw: bool,
+    // comment lines of the current record; after the first description only if `keep_comments`
+    comments: Vec<String>,
+    // comment lines of the record returned last
+    record_comments: Vec<String>,
+    keep_comments: bool,
+    lenient: bool,
+    unnamed: usize,
 }
 
 impl FastaReader {
     pub fn new(path: &Path) -> Self {
-        let reader = open(&path);
+        Self::try_new(path).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but returns an error instead of panicking if the file
+    /// can't be opened or has content other than blank lines and `;` comments
+    /// before its first description.
+    ///
+    /// The error message names the file. Empty files are not an error; they
+    /// have no entries.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::read::FastaReader;
+    /// use std::path::Path;
+    ///
+    /// assert!(FastaReader::try_new(Path::new("./resources/test.fasta")).is_ok());
+    /// assert!(FastaReader::try_new(Path::new("./resources/does_not_exist.fasta")).is_err());
+    /// ```
+    pub fn try_new(path: &Path) -> Result<Self, FastaError> {
+        Self::open_reader(path, false)
+    }
+
+    /// Creates a reader for FASTA data from any source, e.g. stdin, a network
+    /// stream or a byte slice.
+    ///
+    /// Gzip and zstd compressed data are decompressed like in `try_new`,
+    /// detected by their magic bytes. Errors are as in `try_new`, but their
+    /// messages name no file.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::read::FastaReader;
+    ///
+    /// let data: &[u8] = b">seq1 first\nACGT\nGG\n>seq2\nTTA\n";
+    /// let entries = FastaReader::from_reader(data)
+    ///     .unwrap()
+    ///     .entries()
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(entries[0].sequence, "ACGTGG");
+    /// assert_eq!(entries[1].description, "seq2");
+    /// ```
+    pub fn from_reader<R: Read + 'static>(reader: R) -> Result<Self, FastaError> {
+        Self::from_boxed(decompress(BufReader::new(reader), None)?, false)
+    }
+
+    /// Creates a reader that tries to recover sequences without a description.
+    ///
+    /// Sequence lines found before the first description, or after a blank
+    /// line that ends a record, are yielded as entries with placeholder
+    /// descriptions `>unnamed_1`, `>unnamed_2`, ...
+    /// This is a best-effort salvage of malformed files, not spec-compliant parsing.
+    pub fn new_lenient(path: &Path) -> Self {
+        Self::open_reader(path, true).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn open_reader(path: &Path, lenient: bool) -> Result<Self, FastaError> {
+        let input = try_open(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Could not open {}: {}", path.display(), e),
+            )
+        })?;
+        Self::from_boxed(input, lenient).map_err(|e| match e {
+            FastaError::Parse { kind, message } => FastaError::Parse {
+                kind,
+                message: format!("{}: {}", path.display(), message),
+            },
+            e => e,
+        })
+    }
+
+    fn from_boxed(reader: Box<dyn Read>, lenient: bool) -> Result<Self, FastaError> {
         let mut res = FastaReader {
-            lines: BufReader::new(reader).lines(),
+            input: Some(BufReader::new(reader)),
+            line_buf: Vec::new(),
             description: None,
             seq_buf: String::new(),
+            raw_buf: Vec::new(),
+            raw_record: Vec::new(),
+            keep_raw: false,
+            comments: Vec::new(),
+            record_comments: Vec::new(),
+            keep_comments: false,
+            lenient,
+            unnamed: 0,
         };
 
         // find first description
-        while res.description == None {
-            match res.lines.next() {
-                Some(s) => {
-                    let line = s.unwrap();
-                    if line.starts_with('>') {
-                        res.description = Some(line.to_string());
-                    }
+        while res.description.is_none() {
+            let line = match res.next_line() {
+                Some(line) => line?,
+                // an empty file has no entries
+                None => break,
+            };
+            // anything before the first description belongs to the first record
+            res.raw_buf.extend_from_slice(&res.line_buf);
+            if line.starts_with('>') {
+                res.description = Some(line);
+            } else if line.starts_with(';') {
+                res.comments.push(line);
+            } else if line.trim().is_empty() {
+                continue;
+            } else if res.lenient {
+                res.description = Some(res.placeholder());
+                res.seq_buf.push_str(&line);
+            } else {
+                return Err(FastaError::parse(
+                    ErrorKind::NoDescription,
+                    format!(
+                        "Expected a description line starting with `>`, found: {:?}",
+                        line.chars().take(40).collect::<String>()
+                    ),
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Turns the reader into an iterator over `FastaEntry`s, whose
+    /// description and sequence are accessed by name rather than by position.
+    ///
+    /// Unlike the `[description, sequence]` pairs of the reader, the
+    /// description does not start with `>`.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::read::FastaReader;
+    /// use std::path::Path;
+    ///
+    /// let mut entries = FastaReader::new(Path::new("./resources/test.fasta")).entries();
+    /// let entry = entries.next().unwrap().unwrap();
+    /// assert!(entry.description.starts_with("sp|Q2HZH0|"));
+    /// assert_eq!(entry.sequence.len(), 120);
+    /// assert_eq!(entries.count(), 2);
+    /// ```
+    pub fn entries(self) -> impl Iterator<Item = Result<FastaEntry, FastaError>> {
+        self.map(|record| {
+            record.map(|[description, sequence]| FastaEntry {
+                description: match description.strip_prefix('>') {
+                    Some(header) => header.to_string(),
+                    None => description,
+                },
+                sequence,
+            })
+        })
+    }
+
+    /// Turns the reader into an iterator that also yields the raw bytes of
+    /// each record, including line breaks.
+    ///
+    /// Concatenating the raw bytes of all records reproduces the input,
+    /// where anything before the first description is part of the first record.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::read::FastaReader;
+    /// use std::path::Path;
+    ///
+    /// let path = Path::new("./resources/test.fasta");
+    /// let mut raw = Vec::new();
+    /// for record in FastaReader::new(path).with_raw() {
+    ///     let (bytes, entry) = record.unwrap();
+    ///     raw.extend(bytes);
+    /// }
+    /// assert_eq!(raw, std::fs::read(path).unwrap());
+    /// ```
+    pub fn with_raw(mut self) -> impl Iterator<Item = Result<(Vec<u8>, FastaEntry), FastaError>> {
+        self.keep_raw = true;
+        std::iter::from_fn(move || {
+            self.next().map(|record| {
+                Ok((
+                    std::mem::take(&mut self.raw_record),
+                    FastaEntry::from(record?),
+                ))
+            })
+        })
+    }
+
+    /// Turns the reader into an iterator that also yields the `;` comment
+    /// lines of each record, including the `;`.
+    ///
+    /// Comments belong to the record whose description precedes them; those
+    /// before the first description belong to the first record.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::read::FastaReader;
+    /// use std::path::Path;
+    ///
+    /// let path = Path::new("./resources/test_comments.fasta");
+    /// let (comments, entry) = FastaReader::new(path).with_comments().next().unwrap().unwrap();
+    /// assert_eq!(comments.len(), 2);
+    /// assert_eq!(comments[1], ";interleukin-1 beta");
+    /// assert_eq!(entry.sequence.len(), 120);
+    /// ```
+    pub fn with_comments(
+        mut self,
+    ) -> impl Iterator<Item = Result<(Vec<String>, FastaEntry), FastaError>> {
+        self.keep_comments = true;
+        std::iter::from_fn(move || {
+            self.next().map(|record| {
+                Ok((
+                    std::mem::take(&mut self.record_comments),
+                    FastaEntry::from(record?),
+                ))
+            })
+        })
+    }
+
+    /// Returns the description of the entry the next call to `next` yields,
+    /// without consuming it, or `None` once all entries are read.
+    ///
+    /// # Examples
+    /// ```
+    /// use fasta::read::FastaReader;
+    /// use std::path::Path;
+    ///
+    /// let mut reader = FastaReader::new(Path::new("./resources/test.fasta"));
+    /// while reader.peek_header().map_or(false, |d| d.starts_with(">sp|")) {
+    ///     reader.next();
+    /// }
+    /// assert!(reader.peek_header().unwrap().starts_with(">tr|P93158|"));
+    /// ```
+    pub fn peek_header(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    // Reads the next line without its line break; the raw bytes are left in `line_buf`.
+    fn next_line(&mut self) -> Option<io::Result<String>> {
+        let input = self.input.as_mut()?;
+        self.line_buf.clear();
+        match input.read_until(b'\n', &mut self.line_buf) {
+            Ok(0) => {
+                self.input = None;
+                None
+            }
+            Ok(_) => {
+                let mut line = &self.line_buf[..];
+                if let Some(l) = line.strip_suffix(b"\n") {
+                    line = l.strip_suffix(b"\r").unwrap_or(l);
+                }
+                Some(
+                    String::from_utf8(line.to_vec())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                )
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn placeholder(&mut self) -> String {
+        self.unnamed += 1;
+        format!(">unnamed_{}", self.unnamed)
+    }
+
+    // Reads the next record, passing on I/O errors.
+    pub(crate) fn read_record(&mut self) -> Option<Result<[String; 2], FastaError>> {
+        let mut after_blank = false;
+
+        while let Some(l) = self.next_line() {
+            let line = match l {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.starts_with('>') {
+                return Some(Ok(self.take_record(line)));
+            } else if line.starts_with(';') {
+                if self.keep_comments {
+                    self.comments.push(line);
+                }
+                if self.keep_raw {
+                    self.raw_buf.extend_from_slice(&self.line_buf);
+                }
+                continue;
+            } else if self.lenient && line.trim().is_empty() {
+                after_blank = true;
+            } else if after_blank && !self.seq_buf.is_empty() {
+                // sequence after a finished record; start an unnamed one
+                let description = self.placeholder();
+                let res = self.take_record(description);
+                self.seq_buf.push_str(&line);
+                return Some(Ok(res));
+            }
+            if !line.starts_with('>') {
+                self.seq_buf.push_str(&line);
+                if self.keep_raw {
+                    self.raw_buf.extend_from_slice(&self.line_buf);
                 }
-                None => panic!("Reached EOF in FASTA parsing; No description in file?"),
             }
         }
-        res
+
+        let description = self.description.take()?;
+        self.raw_record = std::mem::take(&mut self.raw_buf);
+        self.record_comments = std::mem::take(&mut self.comments);
+        Some(Ok([description, std::mem::take(&mut self.seq_buf)]))
+    }
+
+    // Returns the current record and starts a new one under `description`,
+    // whose raw bytes are the last line read.
+    fn take_record(&mut self, description: String) -> [String; 2] {
+        self.raw_record = std::mem::replace(&mut self.raw_buf, self.line_buf.clone());
+        self.record_comments = std::mem::take(&mut self.comments);
+        [
+            self.description.replace(description).unwrap(),
+            std::mem::take(&mut self.seq_buf),
+        ]
     }
 }
 
 impl Iterator for FastaReader {
-    type Item = [String; 2];
+    type Item = Result<[String; 2], FastaError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.seq_buf.clear();
+        self.read_record()
+    }
+}
 
-        while let Some(l) = self.lines.next() {
-            let line = l.unwrap();
-            if line.starts_with('>') {
-                let res = [self.description.clone().unwrap(), self.seq_buf.clone()];
-                self.description = Some(line);
-                return Some(res);
-            } else {
-                self.seq_buf.push_str(&line);
+/// Reads a FASTA file on a separate thread and sends its entries over a channel.
+///
+/// At most `buffer` entries are held in the channel; the reading thread
+/// blocks until the receiver catches up. Errors are sent as `Err` values,
+/// after which the thread stops. The thread also stops when the receiver
+/// is dropped.
+///
+/// # Examples
+/// ```
+/// use fasta::read::spawn_reader;
+/// use std::path::Path;
+///
+/// let entries = spawn_reader(Path::new("./resources/test.fasta"), 16);
+/// for entry in entries {
+///     println!("{}", entry.unwrap().description);
+/// }
+/// ```
+pub fn spawn_reader(path: &Path, buffer: usize) -> Receiver<Result<FastaEntry, FastaError>> {
+    let (sender, receiver) = sync_channel(buffer);
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        let mut reader = match FastaReader::try_new(&path) {
+            Ok(reader) => reader,
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        while let Some(record) = reader.read_record() {
+            let failed = record.is_err();
+            if sender.send(record.map(FastaEntry::from)).is_err() || failed {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Iterates over the entries of a gzip compressed FASTA file.
+///
+/// The file is decompressed while reading, regardless of its extension,
+/// so no seeking is needed. Each entry is yielded as a `Result`, and the
+/// file is closed as soon as the end of the stream is reached or the
+/// iterator is dropped.
+///
+/// # Examples
+/// ```
+/// use fasta::read::iter_gz;
+/// use std::path::Path;
+///
+/// for entry in iter_gz(Path::new("./resources/test.fasta.gz")).unwrap() {
+///     println!("{}", entry.unwrap().description);
+/// }
+/// ```
+pub fn iter_gz(
+    path: &Path,
+) -> Result<impl Iterator<Item = Result<FastaEntry, FastaError>>, FastaError> {
+    let decoder = MultiGzDecoder::new(BufReader::new(File::open(path)?));
+    let mut reader = FastaReader::from_boxed(Box::new(decoder), false)?;
+    Ok(std::iter::from_fn(move || {
+        reader.read_record().map(|r| r.map(FastaEntry::from))
+    }))
+}
+
+/// Iterates over the entries of several FASTA files as one stream, in order.
+///
+/// Each file is parsed on its own, so entries never continue across files,
+/// even if a file does not end with a newline. Files are opened one at a
+/// time, and gzip files are decompressed like in `FastaReader::new`.
+/// A file that cannot be opened or has no description yields an error,
+/// after which the next file is read.
+///
+/// # Examples
+/// ```
+/// use fasta::read::multi_reader;
+/// use std::path::Path;
+///
+/// let paths = [
+///     Path::new("./resources/test.fasta"),
+///     Path::new("./resources/test.fasta.gz"),
+/// ];
+/// assert_eq!(multi_reader(&paths).count(), 6);
+/// ```
+pub fn multi_reader(paths: &[&Path]) -> impl Iterator<Item = Result<FastaEntry, FastaError>> {
+    let paths = paths.iter().map(|p| p.to_path_buf()).collect::<Vec<_>>();
+    paths.into_iter().flat_map(|path| {
+        let mut reader = Some(FastaReader::try_new(&path));
+        std::iter::from_fn(move || {
+            if let Some(Err(_)) = reader {
+                return reader.take().and_then(Result::err).map(Err);
+            }
+            match reader.as_mut()? {
+                Ok(reader) => reader.read_record().map(|r| r.map(FastaEntry::from)),
+                Err(_) => None,
+            }
+        })
+    })
+}
+
+/// Reads all entries of a FASTA file into a `Vec`, in file order.
+///
+/// Unlike `FastaMap`, entries with the same id are all kept.
+/// Gzip files are decompressed like in `FastaReader::new`.
+///
+/// # Examples
+/// ```
+/// use fasta::read::read_all;
+/// use std::path::Path;
+///
+/// let entries = read_all(Path::new("./resources/test.fasta.gz")).unwrap();
+/// assert_eq!(entries.len(), 3);
+/// ```
+pub fn read_all(path: &Path) -> io::Result<Vec<FastaEntry>> {
+    let mut reader = FastaReader::try_new(path)?;
+    let mut entries = Vec::new();
+    while let Some(record) = reader.read_record() {
+        entries.push(FastaEntry::from(record?));
+    }
+    Ok(entries)
+}
+
+/// Checks that writing a file with the given options and reading it back
+/// yields the same entries.
+///
+/// The entries are compared as a multiset of (description, sequence) pairs,
+/// so their order does not matter. The file is rewritten in memory.
+/// Options that change the content, like a record separator that is
+/// not blank, make the check fail.
+pub fn verify_roundtrip(input: &Path, write_opts: WriteOptions) -> io::Result<bool> {
+    let mut original = read_all(input)?;
+    let mut writer = FastaWriter::in_memory(write_opts);
+    for entry in &original {
+        writer.write_fasta_entry(entry)?;
+    }
+    let bytes = writer.into_bytes()?;
+    let mut reader = FastaReader::from_boxed(Box::new(io::Cursor::new(bytes)), false)?;
+    let mut rewritten = Vec::new();
+    while let Some(record) = reader.read_record() {
+        rewritten.push(FastaEntry::from(record?));
+    }
+    let by_content = |a: &FastaEntry, b: &FastaEntry| {
+        (&a.description, &a.sequence).cmp(&(&b.description, &b.sequence))
+    };
+    original.sort_by(by_content);
+    rewritten.sort_by(by_content);
+    Ok(original == rewritten)
+}
+
+// Number of bytes read at a time when searching a file backwards.
+const BACKWARD_CHUNK_SIZE: u64 = 64 << 10;
+
+/// Reads the last entry of a file, or returns `None` if the file has no entries.
+///
+/// Uncompressed files are searched backwards from their end for the last
+/// description line, so only the last entry is read. Compressed files are
+/// read from the start.
+///
+/// # Examples
+/// ```
+/// use fasta::read::last_entry;
+/// use std::path::Path;
+///
+/// let last = last_entry(Path::new("./resources/test.fasta")).unwrap().unwrap();
+/// assert!(last.description.starts_with(">tr|H0VS30|"));
+/// ```
+pub fn last_entry(path: &Path) -> io::Result<Option<FastaEntry>> {
+    let offset = if file_compression(path)? != Compression::Plain {
+        None
+    } else {
+        last_description_offset(&mut File::open(path)?, BACKWARD_CHUNK_SIZE)?
+    };
+    let mut reader = match offset {
+        Some(offset) => {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            FastaReader::from_boxed(Box::new(file), false)?
+        }
+        None => FastaReader::try_new(path)?,
+    };
+    let mut last = None;
+    while let Some(record) = reader.read_record() {
+        last = Some(FastaEntry::from(record?));
+    }
+    Ok(last)
+}
+
+// Finds the offset of the last `>` at the start of a line,
+// reading backwards from the end in chunks of `chunk_size` bytes.
+fn last_description_offset(file: &mut File, chunk_size: u64) -> io::Result<Option<u64>> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut buf = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(chunk_size);
+        // one more byte to see whether the first byte of the chunk starts a line
+        let read_start = start.saturating_sub(1);
+        buf.resize((end - read_start) as usize, 0);
+        file.seek(SeekFrom::Start(read_start))?;
+        file.read_exact(&mut buf)?;
+        let skip = (start - read_start) as usize;
+        for i in (skip..buf.len()).rev() {
+            if buf[i] == b'>' && (i == 0 || buf[i - 1] == b'\n') {
+                return Ok(Some(read_start + i as u64));
+            }
+        }
+        end = start;
+    }
+    Ok(None)
+}
+
+// Number of bytes read to estimate the record count of a file.
+const ESTIMATE_SAMPLE_SIZE: usize = 4 << 20;
+
+/// Estimates the number of entries in a FASTA file from its first few megabytes.
+///
+/// The average size of the complete entries found in the sample is
+/// extrapolated to the size of the whole file. For compressed files, the
+/// uncompressed size is extrapolated from the compression
+/// ratio of the sample. Files smaller than the sample are counted exactly.
+/// This is a rough estimate for progress reporting only: files whose entry
+/// sizes vary a lot along the file, or whose first entry is larger than the
+/// sample, are estimated poorly.
+pub fn estimate_record_count(path: &Path) -> io::Result<u64> {
+    estimate_record_count_with(path, ESTIMATE_SAMPLE_SIZE)
+}
+
+fn estimate_record_count_with(path: &Path, sample_size: usize) -> io::Result<u64> {
+    let file_len = fs::metadata(path)?.len();
+    let mut raw = Vec::with_capacity(sample_size.min(file_len as usize));
+    File::open(path)?
+        .take(sample_size as u64)
+        .read_to_end(&mut raw)?;
+
+    let compression = compression_of(&raw, Some(path));
+    let (sample, total_len) = if compression != Compression::Plain {
+        let mut decoded = Vec::new();
+        // a truncated sample fails at its end; keep what was decoded until then
+        let complete = decoder(&raw[..], compression)?
+            .read_to_end(&mut decoded)
+            .is_ok();
+        let total_len = if complete {
+            decoded.len() as u64
+        } else {
+            (file_len as f64 * decoded.len() as f64 / raw.len() as f64) as u64
+        };
+        (decoded, total_len)
+    } else {
+        (raw, file_len)
+    };
+
+    let headers = sample
+        .iter()
+        .enumerate()
+        .filter(|(i, b)| **b == b'>' && (*i == 0 || sample[i - 1] == b'\n'))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if sample.len() as u64 >= total_len || headers.len() < 2 {
+        return Ok(headers.len() as u64);
+    }
+
+    // extrapolate from the complete records, i.e. up to the last header
+    let complete_len = (headers[headers.len() - 1] - headers[0]) as f64;
+    let per_record = complete_len / (headers.len() - 1) as f64;
+    Ok((total_len as f64 / per_record).round() as u64)
+}
+
+/// Computes a checksum over the sequence content of a FASTA file.
+///
+/// The digest is independent of record order, line wrapping, letter case
+/// and descriptions: each sequence is uppercased and hashed on its own and
+/// the per-record hashes are summed. Two files holding the same sequences
+/// therefore yield the same digest. This is meant for comparing files,
+/// not as a cryptographic hash.
+///
+/// # Examples
+/// ```
+/// use fasta::read::content_digest;
+/// use std::path::Path;
+///
+/// assert_eq!(
+///     content_digest(Path::new("./resources/test.fasta")).unwrap(),
+///     content_digest(Path::new("./resources/test_reordered.fasta")).unwrap(),
+/// );
+/// ```
+pub fn content_digest(path: &Path) -> io::Result<String> {
+    let mut digest: u64 = 0;
+    for record in FastaReader::try_new(path)? {
+        let [_description, seq] = record?;
+        digest = digest.wrapping_add(sequence_hash(&seq));
+    }
+    Ok(format!("{:016x}", digest))
+}
+
+/// Lists the byte range occupied by each entry of an uncompressed FASTA file.
+///
+/// Returns `(id, start, end)` per entry in file order, where `start` is the
+/// offset of the description line and `end` the offset of the next
+/// description line, or the file length for the last entry.
+/// Ids are parsed as in `FastaIndex::new`.
+pub fn record_ranges(
+    path: &Path,
+    separator: &str,
+    id_index: usize,
+) -> io::Result<Vec<(String, u64, u64)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut ranges: Vec<(String, u64, u64)> = Vec::new();
+    let mut line_buf = String::new();
+    let mut global_offset: u64 = 0;
+
+    loop {
+        line_buf.clear();
+        let len = reader.read_line(&mut line_buf)?;
+        if len == 0 {
+            break;
+        }
+        if line_buf.starts_with('>') {
+            if let Some(last) = ranges.last_mut() {
+                last.2 = global_offset;
+            }
+            let description = line_buf.trim_end_matches(&['\n', '\r'][..]);
+            let id = seq_id_from_description(description, separator, id_index);
+            ranges.push((id.to_string(), global_offset, global_offset));
+        }
+        global_offset += len as u64;
+    }
+    if let Some(last) = ranges.last_mut() {
+        last.2 = global_offset;
+    }
+    Ok(ranges)
+}
+
+/// Appends entries to an indexed FASTA file and updates its json index.
+///
+/// Ids of the new entries are parsed from their descriptions as in
+/// `FastaIndex::try_new`. If an id is already present, empty or missing, or
+/// anything else fails, the FASTA file is truncated back to its original
+/// length and the index is left untouched. If the index holds sequence
+/// lengths, i.e. it was built with `FastaIndex::new_with_lengths`, the
+/// lengths and layouts of the new entries are added as well. The updated index is written to a temporary file
+/// that then replaces the old one, so readers never see a partial index.
+///
+/// # Examples
+/// ```
+/// use fasta::index::FastaIndex;
+/// use fasta::pieces::FastaEntry;
+/// use fasta::read::append_entries;
+/// use std::path::Path;
+///
+/// let fasta = std::env::temp_dir().join("fasta_append_doctest.fasta");
+/// let index = std::env::temp_dir().join("fasta_append_doctest.index");
+/// std::fs::copy("./resources/test.fasta", &fasta).unwrap();
+/// FastaIndex::new(&fasta, "|", 1).to_json(&index).unwrap();
+///
+/// let new_entry = FastaEntry {
+///     description: ">sp|P01584|IL1B_HUMAN Interleukin-1 beta".to_string(),
+///     sequence: "MAEVPELASEMMAYYSGNEDDLFFEADGPKQMKCSFQDLDLCPLDGGIQLRISDHHYSKG".to_string(),
+/// };
+/// append_entries(&fasta, &index, vec![new_entry].into_iter(), "|", 1).unwrap();
+/// assert!(FastaIndex::from_json(&index).unwrap().id_to_offset.contains_key("P01584"));
+/// ```
+pub fn append_entries(
+    fasta: &Path,
+    index: &Path,
+    new_entries: impl Iterator<Item = FastaEntry>,
+    separator: &str,
+    id_index: usize,
+) -> Result<(), FastaError> {
+    let mut fasta_index = FastaIndex::from_json(index)?;
+    let file_len = fs::metadata(fasta)?.len();
+    let with_lengths = !fasta_index.id_to_length.is_empty();
+
+    let write_entries = || -> Result<(), FastaError> {
+        let mut writer = FastaWriter::append(fasta, WriteOptions::default())?;
+        for entry in new_entries {
+            let description = format!(
+                ">{}",
+                entry
+                    .description
+                    .strip_prefix('>')
+                    .unwrap_or(&entry.description)
+            );
+            let id = checked_seq_id(&description, separator, id_index)?.to_string();
+            if fasta_index.id_to_offset.contains_key(&id) {
+                return Err(FastaError::parse(
+                    ErrorKind::DuplicateId,
+                    format!("Multiple entries found for id: {:?}", id),
+                ));
+            }
+            let offset = file_len + writer.bytes_written();
+            writer.write_entry(&description, &entry.sequence)?;
+            if with_lengths {
+                let length = entry.sequence.len();
+                let layout = SequenceLayout::wrapped(
+                    length as u64,
+                    offset + description.len() as u64 + 1,
+                    DEFAULT_WIDTH as u64,
+                );
+                fasta_index.id_to_length.insert(id.clone(), length);
+                fasta_index.id_to_layout.insert(id.clone(), layout);
+            }
+            fasta_index.id_to_offset.insert(id, offset);
+        }
+        writer.finish()?;
+        Ok(())
+    };
+
+    if let Err(e) = write_entries() {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(fasta)?
+            .set_len(file_len)?;
+        return Err(e);
+    }
+
+    let mut tmp_path = index.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = Path::new(&tmp_path);
+    fasta_index.to_json(tmp_path)?;
+    fs::rename(tmp_path, index)?;
+    Ok(())
+}
+
+// Streams the entries of `input` through `f` and writes those it returns.
+fn map_entries<F>(input: &Path, output: &Path, mut f: F) -> io::Result<usize>
+where
+    F: FnMut(FastaEntry) -> Option<FastaEntry>,
+{
+    let mut writer = FastaWriter::new(output)?;
+    let mut written = 0;
+    for record in FastaReader::try_new(input)? {
+        if let Some(entry) = f(FastaEntry::from(record?)) {
+            writer.write_fasta_entry(&entry)?;
+            written += 1;
+        }
+    }
+    writer.finish()?;
+    Ok(written)
+}
+
+/// Converts all RNA sequences of a file to DNA, see `FastaEntry::to_dna`.
+///
+/// Returns the number of entries written.
+pub fn normalize_to_dna(input: &Path, output: &Path) -> io::Result<usize> {
+    map_entries(input, output, |entry| Some(entry.to_dna()))
+}
+
+/// Rewrites a file with `\n` line breaks and without blank lines.
+///
+/// Files that mix `\n` and `\r\n` line breaks, or separate some
+/// records by blank lines and others not, are written uniformly. The file is
+/// read like by `FastaReader::new_lenient`, so sequences without a description
+/// are written under its placeholder descriptions `>unnamed_1`, ..., and the
+/// output can be read by `FastaReader::try_new`. Each sequence is wrapped at the
+/// length of its first line, so uniformly wrapped entries keep their layout;
+/// `;` comment lines are dropped. Returns the number of entries written.
+///
+/// # Examples
+/// ```
+/// use fasta::read::normalize_terminators;
+///
+/// let input = std::env::temp_dir().join("fasta_normalize_terminators_doctest.fasta");
+/// let output = std::env::temp_dir().join("fasta_normalize_terminators_doctest_out.fasta");
+/// std::fs::write(&input, ">a\r\nAC\r\nGT\r\n\r\n>b\nGG").unwrap();
+/// assert_eq!(normalize_terminators(&input, &output).unwrap(), 2);
+/// assert_eq!(std::fs::read_to_string(&output).unwrap(), ">a\nAC\nGT\n>b\nGG\n");
+/// ```
+pub fn normalize_terminators(input: &Path, output: &Path) -> io::Result<usize> {
+    let mut writer = FastaWriter::new(output)?;
+    let mut written = 0;
+    for record in FastaReader::open_reader(input, true)?.with_raw() {
+        let (raw, entry) = record?;
+        writer.set_width(raw_line_width(&raw));
+        writer.write_fasta_entry(&entry)?;
+        written += 1;
+    }
+    writer.finish()?;
+    Ok(written)
+}
+
+// Finds the length of the first sequence line of a raw record if another
+// sequence line follows it, or 0 if the sequence is on a single line.
+fn raw_line_width(raw: &[u8]) -> usize {
+    let mut lines = raw
+        .split(|b| *b == b'\n' || *b == b'\r')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .filter(|line| !line.starts_with(b">") && !line.starts_with(b";"));
+    match (lines.next(), lines.next()) {
+        (Some(first), Some(_)) => first.len(),
+        _ => 0,
+    }
+}
+
+/// Replaces characters in all sequences of a file, e.g. `('U', 'T')` or `('*', 'X')`.
+///
+/// Each pair of `table` replaces one character by another; descriptions are
+/// not changed. Replacements apply to the input characters only, so they are
+/// not chained, and if a character occurs in several pairs the first one is
+/// used. Replacing a character by several, or several by one, is not supported.
+/// Returns the number of entries written.
+///
+/// # Examples
+/// ```
+/// use fasta::read::substitute;
+/// use std::path::Path;
+///
+/// let outpath = std::env::temp_dir().join("fasta_substitute_doctest.fasta");
+/// let written =
+///     substitute(Path::new("./resources/test.fasta"), &outpath, &[('*', 'X'), ('.', '-')])
+///         .unwrap();
+/// assert_eq!(written, 3);
+/// ```
+pub fn substitute(input: &Path, output: &Path, table: &[(char, char)]) -> io::Result<usize> {
+    let mut lookup = HashMap::new();
+    for (from, to) in table {
+        lookup.entry(*from).or_insert(*to);
+    }
+    map_entries(input, output, |entry| {
+        Some(entry.map_sequence(|c| *lookup.get(&c).unwrap_or(&c)))
+    })
+}
+
+/// Cuts all sequences of a file to at most `max_len` residues.
+///
+/// Shorter sequences are written unchanged. Returns the number of entries written.
+pub fn truncate_sequences(input: &Path, output: &Path, max_len: usize) -> io::Result<usize> {
+    map_entries(input, output, |entry| Some(entry.truncate(max_len)))
+}
+
+/// Returns the ids of the entries whose `FastaEntry::complexity_ratio`
+/// is below `threshold`, in file order.
+///
+/// Ids are the first word of the description. Ratios of short sequences
+/// are unreliable, see `FastaEntry::complexity_ratio`.
+pub fn low_complexity_ids_by_compression(path: &Path, threshold: f64) -> io::Result<Vec<String>> {
+    let mut reader = FastaReader::try_new(path)?;
+    let mut ids = Vec::new();
+    while let Some(record) = reader.read_record() {
+        let entry = FastaEntry::from(record?);
+        if entry.complexity_ratio() < threshold {
+            ids.push(seq_id_from_description(&entry.description, " ", 0).to_string());
+        }
+    }
+    Ok(ids)
+}
+
+/// Trims the sequences of a file to the `[start, end)` coordinates given per id,
+/// see `FastaEntry::subsequence`.
+///
+/// Ids are the first word of the description. Entries whose id is not
+/// in `coords` are written unchanged if `keep_unlisted` is set, and
+/// dropped otherwise. Returns the number of entries written.
+///
+/// # Examples
+/// ```
+/// use fasta::read::trim_by_coords;
+/// use std::collections::HashMap;
+/// use std::path::Path;
+///
+/// let outpath = std::env::temp_dir().join("fasta_trim_by_coords_doctest.fasta");
+/// let mut coords = HashMap::new();
+/// coords.insert("sp|Q2HZH0|IL1B_PUSHI".to_string(), (20, 40));
+/// let written =
+///     trim_by_coords(Path::new("./resources/test.fasta"), &coords, &outpath, false).unwrap();
+/// assert_eq!(written, 1);
+/// ```
+pub fn trim_by_coords(
+    input: &Path,
+    coords: &HashMap<String, (usize, usize)>,
+    output: &Path,
+    keep_unlisted: bool,
+) -> io::Result<usize> {
+    map_entries(input, output, |entry| {
+        match coords.get(seq_id_from_description(&entry.description, " ", 0)) {
+            Some(&(start, end)) => Some(entry.subsequence(start, end)),
+            None if keep_unlisted => Some(entry),
+            None => None,
+        }
+    })
+}
+
+/// Pads all sequences of a file to `len` residues, see `FastaEntry::pad_to`.
+///
+/// Longer sequences are cut to `len` residues if `truncate` is set,
+/// and written unchanged otherwise. Returns the number of entries written.
+pub fn pad_sequences(
+    input: &Path,
+    output: &Path,
+    len: usize,
+    fill: char,
+    align: PadAlign,
+    truncate: bool,
+) -> io::Result<usize> {
+    map_entries(input, output, |entry| {
+        let entry = entry.pad_to(len, fill, align);
+        Some(if truncate { entry.truncate(len) } else { entry })
+    })
+}
+
+/// Writes the descriptions and the sequences of a file to two separate files.
+///
+/// Both outputs hold one line per entry, in the same order; descriptions
+/// are written without the leading `>`. Returns the number of entries.
+/// `join_columns` reverses the split.
+pub fn split_columns(input: &Path, descr_out: &Path, seq_out: &Path) -> io::Result<usize> {
+    let mut descriptions = BufWriter::new(File::create(descr_out)?);
+    let mut sequences = BufWriter::new(File::create(seq_out)?);
+    let mut count = 0;
+    for record in FastaReader::try_new(input)? {
+        let [description, seq] = record?;
+        writeln!(descriptions, "{}", &description[1..])?;
+        writeln!(sequences, "{}", seq)?;
+        count += 1;
+    }
+    descriptions.flush()?;
+    sequences.flush()?;
+    Ok(count)
+}
+
+/// Writes one `id<TAB>sequence` line per entry, with unwrapped sequences.
+///
+/// Ids are parsed as in `FastaIndex::new`; only the id is written, so tabs
+/// in the rest of the description do not break the table.
+/// Returns the number of entries written.
+pub fn fasta_to_tsv(
+    input: &Path,
+    output: &Path,
+    separator: &str,
+    id_index: usize,
+) -> io::Result<usize> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    let mut count = 0;
+    for record in FastaReader::try_new(input)? {
+        let [description, seq] = record?;
+        let id = seq_id_from_description(&description, separator, id_index);
+        writeln!(writer, "{}\t{}", id, seq)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Writes the `id<TAB>sequence` lines of a table as FASTA, the inverse of `fasta_to_tsv`.
+///
+/// Sequences are wrapped at `wrap_width` residues, 0 disables wrapping.
+/// Blank lines are skipped; lines without a tab are an error.
+/// Returns the number of entries written.
+pub fn tsv_to_fasta(input: &Path, output: &Path, wrap_width: usize) -> io::Result<usize> {
+    let mut writer = FastaWriter::with_width(output, wrap_width)?;
+    let mut count = 0;
+    for (line_nr, line) in BufReader::new(File::open(input)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (id, seq) = line.split_once('\t').ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No tab in line {} of {:?}.", line_nr + 1, input),
+            )
+        })?;
+        writer.write_entry(id, seq.trim_end())?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+/// Joins description and sequence files written by `split_columns` into FASTA.
+///
+/// Fails if the two files have a different number of lines.
+/// Returns the number of entries written.
+pub fn join_columns(descr_in: &Path, seq_in: &Path, output: &Path) -> io::Result<usize> {
+    let mut descriptions = BufReader::new(File::open(descr_in)?).lines();
+    let mut sequences = BufReader::new(File::open(seq_in)?).lines();
+    let mut writer = FastaWriter::new(output)?;
+    let mut count = 0;
+    loop {
+        match (descriptions.next(), sequences.next()) {
+            (Some(description), Some(seq)) => {
+                writer.write_entry(&description?, &seq?)?;
+                count += 1;
+            }
+            (None, None) => break,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{:?} and {:?} have different numbers of lines.",
+                        descr_in, seq_in
+                    ),
+                ))
             }
         }
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+/// Streams all entries of a file through a `TransformPipeline`.
+///
+/// Returns the number of entries written.
+pub fn apply_pipeline(
+    input: &Path,
+    output: &Path,
+    pipeline: &TransformPipeline,
+) -> io::Result<usize> {
+    map_entries(input, output, |entry| Some(pipeline.apply(entry)))
+}
+
+/// The formatting problems found by `validate`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// The number of entries in the file.
+    pub entries: usize,
+    /// Entries whose sequence lines do not all have the same length, except
+    /// for a shorter last line, as the id and the distinct line lengths in
+    /// file order. `samtools faidx` cannot index such entries.
+    pub inconsistent_line_widths: Vec<(String, Vec<usize>)>,
+}
+
+impl ValidationReport {
+    /// Whether no problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.inconsistent_line_widths.is_empty()
+    }
 
-        match self.seq_buf.len() {
-            0 => None,
-            _ => Some([self.description.clone().unwrap(), self.seq_buf.clone()]),
+    // Records the entry if its line lengths are inconsistent.
+    fn check_line_widths(&mut self, entry: Option<(String, Vec<usize>)>) {
+        let (id, mut widths) = match entry {
+            Some(entry) => entry,
+            None => return,
+        };
+        // trailing blank lines only separate entries
+        while widths.last() == Some(&0) {
+            widths.pop();
+        }
+        let consistent = match widths.split_last() {
+            Some((last, interior)) if !interior.is_empty() => {
+                interior.iter().all(|w| *w == interior[0]) && *last <= interior[0]
+            }
+            _ => true,
+        };
+        if !consistent {
+            let mut distinct = Vec::new();
+            for w in widths {
+                if !distinct.contains(&w) {
+                    distinct.push(w);
+                }
+            }
+            self.inconsistent_line_widths.push((id, distinct));
         }
     }
 }
+
+/// Checks the formatting of a FASTA file line by line.
+///
+/// Ids are the first word of the description. Line lengths do not include
+/// line breaks, and blank lines within an entry count as lines of length 0.
+/// `;` comment lines are skipped, like by `FastaReader`.
+///
+/// # Examples
+/// ```
+/// use fasta::read::validate;
+/// use std::path::Path;
+///
+/// let report = validate(Path::new("./resources/test.fasta")).unwrap();
+/// assert_eq!(report.entries, 3);
+/// assert!(report.is_valid());
+/// ```
+pub fn validate(path: &Path) -> io::Result<ValidationReport> {
+    let mut report = ValidationReport::default();
+    let mut current: Option<(String, Vec<usize>)> = None;
+    for line in BufReader::new(try_open(path)?).lines() {
+        let line = line?;
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if line.starts_with('>') {
+            report.check_line_widths(current.take());
+            report.entries += 1;
+            let id = seq_id_from_description(line, " ", 0).to_string();
+            current = Some((id, Vec::new()));
+        } else if line.starts_with(';') {
+            continue;
+        } else if let Some((_, widths)) = current.as_mut() {
+            widths.push(line.len());
+        }
+    }
+    report.check_line_widths(current);
+    Ok(report)
+}
+
+/// Checks that the sequences of all entries are in `alphabet`, see `FastaEntry::validate`.
+///
+/// Stops at the first invalid character and returns the id (the first word
+/// of the description) of its entry, the character and its 0-based position
+/// in the sequence. Only the current entry is kept in memory.
+///
+/// # Examples
+/// ```
+/// use fasta::helpers::Alphabet;
+/// use fasta::read::validate_alphabet;
+/// use std::path::Path;
+///
+/// let path = Path::new("./resources/test.fasta");
+/// assert_eq!(validate_alphabet(path, Alphabet::Protein).unwrap(), Ok(()));
+/// let (id, c, pos) = validate_alphabet(path, Alphabet::Dna).unwrap().unwrap_err();
+/// assert_eq!((c, pos), ('M', 0));
+/// ```
+pub fn validate_alphabet(
+    path: &Path,
+    alphabet: Alphabet,
+) -> io::Result<Result<(), (String, char, usize)>> {
+    for record in FastaReader::try_new(path)? {
+        let entry = FastaEntry::from(record?);
+        if let Err((c, pos)) = entry.validate(alphabet) {
+            let id = seq_id_from_description(&entry.description, " ", 0);
+            return Ok(Err((id.to_string(), c, pos)));
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// The characters found by `charset_audit`.
+#[derive(Debug, Default, PartialEq)]
+pub struct CharsetReport {
+    /// The distinct characters of all sequence lines with their counts.
+    pub sequence_chars: HashMap<char, usize>,
+    /// The number of control characters, like NUL or a stray `\r`,
+    /// in descriptions and sequences, excluding line breaks.
+    pub control_chars: usize,
+    /// The number of characters outside of ASCII in descriptions and sequences.
+    pub non_ascii_chars: usize,
+    /// The number of lines that are not valid UTF-8; their invalid bytes are
+    /// counted as U+FFFD replacement characters.
+    pub invalid_utf8_lines: usize,
+}
+
+impl CharsetReport {
+    /// Whether the file holds only printable ASCII characters besides line breaks.
+    pub fn is_clean(&self) -> bool {
+        self.control_chars == 0 && self.non_ascii_chars == 0 && self.invalid_utf8_lines == 0
+    }
+}
+
+/// Counts the characters of a FASTA file, to choose an alphabet or to spot
+/// binary or otherwise unexpected content.
+///
+/// Unlike `validate_alphabet`, this does not check against a fixed alphabet,
+/// and invalid UTF-8 is reported rather than an error. Lines starting with
+/// `>` are descriptions, all other lines sequence.
+///
+/// # Examples
+/// ```
+/// use fasta::read::charset_audit;
+/// use std::path::Path;
+///
+/// let report = charset_audit(Path::new("./resources/test.fasta")).unwrap();
+/// assert!(report.is_clean());
+/// assert!(report.sequence_chars.contains_key(&'M'));
+/// ```
+pub fn charset_audit(path: &Path) -> io::Result<CharsetReport> {
+    let mut report = CharsetReport::default();
+    let mut reader = BufReader::new(try_open(path)?);
+    let mut buf = Vec::new();
+    while reader.read_until(b'\n', &mut buf)? > 0 {
+        let mut bytes = &buf[..];
+        if let Some(b) = bytes.strip_suffix(b"\n") {
+            bytes = b.strip_suffix(b"\r").unwrap_or(b);
+        }
+        let line = String::from_utf8_lossy(bytes);
+        if let std::borrow::Cow::Owned(_) = line {
+            report.invalid_utf8_lines += 1;
+        }
+        let is_description = line.starts_with('>');
+        for c in line.chars() {
+            if c.is_control() {
+                report.control_chars += 1;
+            } else if !c.is_ascii() {
+                report.non_ascii_chars += 1;
+            }
+            if !is_description {
+                *report.sequence_chars.entry(c).or_insert(0) += 1;
+            }
+        }
+        buf.clear();
+    }
+    Ok(report)
+}
+
+/// Computes the GC content over all entries of a file, as in `helpers::gc_content`.
+///
+/// Each base counts the same, so long entries weigh more than short ones.
+/// The entries are streamed, one at a time.
+///
+/// # Examples
+/// ```
+/// use fasta::read::total_gc_content;
+/// use std::path::Path;
+///
+/// let gc = total_gc_content(Path::new("./resources/test.fasta")).unwrap();
+/// assert!(gc > 0.0 && gc < 1.0);
+/// ```
+pub fn total_gc_content(path: &Path) -> io::Result<f64> {
+    let (mut gc, mut total) = (0, 0);
+    for record in FastaReader::try_new(path)? {
+        let [_, seq] = record?;
+        let (entry_gc, entry_total) = gc_counts(&seq);
+        gc += entry_gc;
+        total += entry_total;
+    }
+    if total == 0 {
+        Ok(0.0)
+    } else {
+        Ok(gc as f64 / total as f64)
+    }
+}
+
+/// Writes a TSV table of quality metrics, one row per entry in file order.
+///
+/// The columns are `id`, `length`, `gc_content` (see `helpers::gc_content`),
+/// `n_count` (`N` or `n` residues) and `lowercase_fraction` (see
+/// `FastaEntry::masked_fraction`), preceded by a header row. Fractions are
+/// written with four decimals. Ids are parsed as in `FastaIndex::new`.
+/// Returns the number of entries.
+pub fn metrics_report(
+    path: &Path,
+    separator: &str,
+    id_index: usize,
+    output: &Path,
+) -> io::Result<usize> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    writeln!(
+        writer,
+        "id\tlength\tgc_content\tn_count\tlowercase_fraction"
+    )?;
+    let mut count = 0;
+    for record in FastaReader::try_new(path)? {
+        let entry = FastaEntry::from(record?);
+        let n_count = entry
+            .sequence
+            .bytes()
+            .filter(|b| b.eq_ignore_ascii_case(&b'N'))
+            .count();
+        writeln!(
+            writer,
+            "{}\t{}\t{:.4}\t{}\t{:.4}",
+            seq_id_from_description(&entry.description, separator, id_index),
+            entry.sequence.len(),
+            gc_content(&entry.sequence),
+            n_count,
+            entry.masked_fraction()
+        )?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Writes the `n` entries with the longest sequences.
+///
+/// Only the `n` longest entries seen so far are kept, in a min-heap by
+/// length, so memory use does not depend on the size of the file. The
+/// entries are written by descending length; of entries with the same
+/// length, those earlier in the file are preferred and written first.
+/// Returns the number of entries written.
+pub fn top_n_longest(input: &Path, output: &Path, n: usize) -> io::Result<usize> {
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    if n > 0 {
+        for (i, record) in FastaReader::try_new(input)?.enumerate() {
+            let record = record?;
+            // the heap pops the shortest and, of those, the latest entry
+            heap.push((Reverse((record[1].len(), Reverse(i))), record));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+    }
+
+    let mut writer = FastaWriter::new(output)?;
+    let longest = heap.into_sorted_vec();
+    for (_, [description, seq]) in &longest {
+        writer.write_entry(description, seq)?;
+    }
+    writer.finish()?;
+    Ok(longest.len())
+}
+
+/// Computes the majority-rule consensus of an alignment.
+///
+/// For each column, the most common character, ignoring case, is used if
+/// its fraction of all sequences exceeds `threshold`, and `N` otherwise.
+/// Gaps are counted like residues, so a column of mostly gaps yields `-`.
+/// Ties go to the character that sorts first. Only per-column counts are
+/// kept in memory, not the sequences. All sequences must have the same
+/// length. The consensus is described as `>consensus`.
+///
+/// # Examples
+/// ```
+/// use fasta::read::consensus;
+///
+/// let path = std::env::temp_dir().join("fasta_consensus_doctest.fasta");
+/// std::fs::write(&path, ">a\nACGT\n>b\nACGA\n>c\nAC-C\n").unwrap();
+/// assert_eq!(consensus(&path, 0.5).unwrap().sequence, "ACGN");
+/// ```
+pub fn consensus(path: &Path, threshold: f64) -> io::Result<FastaEntry> {
+    let mut columns: Vec<HashMap<u8, usize>> = Vec::new();
+    let mut count = 0;
+    let mut reader = FastaReader::try_new(path)?;
+    while let Some(record) = reader.read_record() {
+        let [description, seq] = record?;
+        if count == 0 {
+            columns.resize_with(seq.len(), HashMap::new);
+        } else if seq.len() != columns.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Sequence of {:?} has length {}, expected {}.",
+                    description,
+                    seq.len(),
+                    columns.len()
+                ),
+            ));
+        }
+        for (column, b) in columns.iter_mut().zip(seq.bytes()) {
+            *column.entry(b.to_ascii_uppercase()).or_insert(0) += 1;
+        }
+        count += 1;
+    }
+
+    let sequence = columns
+        .iter()
+        .map(|column| {
+            let (b, n) = column
+                .iter()
+                .max_by(|(b1, n1), (b2, n2)| n1.cmp(n2).then(b2.cmp(b1)))
+                .expect("every column has a count");
+            if *n as f64 / count as f64 > threshold {
+                *b as char
+            } else {
+                'N'
+            }
+        })
+        .collect();
+    Ok(FastaEntry {
+        description: ">consensus".to_string(),
+        sequence,
+    })
+}
+
+/// Removes the columns of an alignment that are gaps (`-` or `.`) in every sequence.
+///
+/// The whole alignment is held in memory. All sequences must have the same
+/// length. Returns the number of columns removed.
+pub fn remove_all_gap_columns(input: &Path, output: &Path) -> io::Result<usize> {
+    let entries = read_all(input)?;
+    let width = entries.first().map_or(0, |e| e.sequence.len());
+    if let Some(entry) = entries.iter().find(|e| e.sequence.len() != width) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Sequence of {:?} has length {}, expected {}.",
+                entry.description,
+                entry.sequence.len(),
+                width
+            ),
+        ));
+    }
+    let keep = (0..width)
+        .map(|i| {
+            entries
+                .iter()
+                .any(|e| !matches!(e.sequence.as_bytes()[i], b'-' | b'.'))
+        })
+        .collect::<Vec<_>>();
+
+    let mut writer = FastaWriter::new(output)?;
+    for entry in &entries {
+        let sequence = entry
+            .sequence
+            .bytes()
+            .zip(&keep)
+            .filter(|(_, keep)| **keep)
+            .map(|(b, _)| b as char)
+            .collect::<String>();
+        writer.write_entry(&entry.description, &sequence)?;
+    }
+    writer.finish()?;
+    Ok(keep.iter().filter(|keep| !**keep).count())
+}
+
+/// Reports entries whose sequence length lies outside `min..=max`.
+///
+/// Returns the id (the first word of the description) and the length of
+/// each such entry, in file order. Only the outliers are kept in memory.
+pub fn flag_length_outliers(
+    path: &Path,
+    min: usize,
+    max: usize,
+) -> io::Result<Vec<(String, usize)>> {
+    let mut outliers = Vec::new();
+    for record in FastaReader::try_new(path)? {
+        let [description, seq] = record?;
+        if seq.len() < min || seq.len() > max {
+            let id = seq_id_from_description(&description, " ", 0);
+            outliers.push((id.to_string(), seq.len()));
+        }
+    }
+    Ok(outliers)
+}
+
+/// Writes the entries whose description contains `needle`.
+///
+/// Returns the number of entries written.
+///
+/// # Examples
+/// ```
+/// use fasta::read::filter_by_description;
+/// use std::path::Path;
+///
+/// let outpath = std::env::temp_dir().join("fasta_filter_descr_doctest.fasta");
+/// let written =
+///     filter_by_description(Path::new("./resources/test.fasta"), &outpath, "kinase", true)
+///         .unwrap();
+/// assert_eq!(written, 1);
+/// ```
+pub fn filter_by_description(
+    input: &Path,
+    output: &Path,
+    needle: &str,
+    case_insensitive: bool,
+) -> io::Result<usize> {
+    let needle = if case_insensitive {
+        needle.to_lowercase()
+    } else {
+        needle.to_string()
+    };
+    map_entries(input, output, |entry| {
+        let description = &entry.description[1..];
+        let found = if case_insensitive {
+            description.to_lowercase().contains(&needle)
+        } else {
+            description.contains(&needle)
+        };
+        if found {
+            Some(entry)
+        } else {
+            None
+        }
+    })
+}
+
+/// Writes the entries whose description matches `pattern`.
+///
+/// Returns the number of entries written.
+#[cfg(feature = "regex")]
+pub fn filter_by_description_regex(
+    input: &Path,
+    output: &Path,
+    pattern: &Regex,
+) -> io::Result<usize> {
+    map_entries(input, output, |entry| {
+        if pattern.is_match(&entry.description[1..]) {
+            Some(entry)
+        } else {
+            None
+        }
+    })
+}
+
+/// Writes the entries for which `predicate` returns true.
+///
+/// Returns the number of entries written.
+///
+/// # Examples
+/// ```
+/// use fasta::read::filter;
+/// use std::path::Path;
+///
+/// let outpath = std::env::temp_dir().join("fasta_filter_doctest.fasta");
+/// let is_prime = |n: usize| n > 1 && (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0);
+/// let written = filter(Path::new("./resources/test.fasta"), &outpath, |entry| {
+///     is_prime(entry.sequence.len())
+/// })
+/// .unwrap();
+/// assert!(written <= 3);
+/// ```
+pub fn filter<F>(input: &Path, output: &Path, mut predicate: F) -> io::Result<usize>
+where
+    F: FnMut(&FastaEntry) -> bool,
+{
+    map_entries(input, output, |entry| {
+        if predicate(&entry) {
+            Some(entry)
+        } else {
+            None
+        }
+    })
+}
+
+// Number of output files `partition` keeps open at a time.
+const MAX_OPEN_PARTITIONS: usize = 64;
+
+/// Writes each entry to `output_dir/{group}.fasta`, where the group of
+/// an entry is returned by `group_fn`.
+///
+/// Existing files of the groups are overwritten. At most 64 files are kept
+/// open at a time; the least recently used file is closed to make room for
+/// another, and reopened in append mode when needed again. Groups that are empty or contain path
+/// separators are an error. Returns the number of entries written per group.
+///
+/// # Examples
+/// ```
+/// use fasta::read::partition;
+/// use std::path::Path;
+///
+/// let outdir = std::env::temp_dir().join("fasta_partition_doctest");
+/// std::fs::create_dir_all(&outdir).unwrap();
+/// let counts = partition(Path::new("./resources/test.fasta"), &outdir, |entry| {
+///     entry.description[1..3].to_string()
+/// })
+/// .unwrap();
+/// assert_eq!(counts["tr"], 2);
+/// assert!(outdir.join("sp.fasta").exists());
+/// ```
+pub fn partition<F>(
+    input: &Path,
+    output_dir: &Path,
+    group_fn: F,
+) -> io::Result<HashMap<String, usize>>
+where
+    F: Fn(&FastaEntry) -> String,
+{
+    partition_with(input, output_dir, group_fn, MAX_OPEN_PARTITIONS)
+}
+
+fn partition_with<F>(
+    input: &Path,
+    output_dir: &Path,
+    group_fn: F,
+    max_open: usize,
+) -> io::Result<HashMap<String, usize>>
+where
+    F: Fn(&FastaEntry) -> String,
+{
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    // the open writers with the number of the entry they last wrote
+    let mut writers: HashMap<String, (FastaWriter, usize)> = HashMap::new();
+    let mut reader = FastaReader::try_new(input)?;
+    let mut entry_number = 0;
+    while let Some(record) = reader.read_record() {
+        let entry = FastaEntry::from(record?);
+        entry_number += 1;
+        let group = group_fn(&entry);
+        let path = group_path(output_dir, &group)?;
+        if !writers.contains_key(&group) {
+            if writers.len() >= max_open.max(1) {
+                let least_recent = writers
+                    .iter()
+                    .min_by_key(|(_, (_, last_used))| *last_used)
+                    .map(|(group, _)| group.clone())
+                    .unwrap();
+                writers.remove(&least_recent).unwrap().0.finish()?;
+            }
+            let writer = if counts.contains_key(&group) {
+                FastaWriter::append(&path, WriteOptions::default())?
+            } else {
+                FastaWriter::new(&path)?
+            };
+            writers.insert(group.clone(), (writer, entry_number));
+        }
+        let (writer, last_used) = writers.get_mut(&group).unwrap();
+        writer.write_fasta_entry(&entry)?;
+        *last_used = entry_number;
+        *counts.entry(group).or_insert(0) += 1;
+    }
+    for (_, (writer, _)) in writers {
+        writer.finish()?;
+    }
+    Ok(counts)
+}
+
+// The output file `output_dir/{group}.fasta` of a group.
+fn group_path(output_dir: &Path, group: &str) -> io::Result<PathBuf> {
+    if group.is_empty() || group.contains(std::path::is_separator) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid group name for output file: {:?}", group),
+        ));
+    }
+    Ok(output_dir.join(format!("{}.fasta", group)))
+}
+
+/// Makes the ids of a file unique by appending a counter to repeated ids.
+///
+/// The first occurrence of an id is kept; later ones get `_2`, `_3`, ...
+/// appended to the id field within the description. Counters already used
+/// by other ids are skipped, so the output can be indexed. Ids are parsed as
+/// in `FastaIndex::new`. Returns the number of entries renamed.
+///
+/// # Examples
+/// ```
+/// use fasta::read::uniquify_ids;
+///
+/// let input = std::env::temp_dir().join("fasta_uniquify_doctest.fasta");
+/// let output = std::env::temp_dir().join("fasta_uniquify_doctest_out.fasta");
+/// std::fs::write(&input, ">sp|P1|A\nAC\n>sp|P1|B\nGG\n").unwrap();
+/// assert_eq!(uniquify_ids(&input, &output, "|", 1).unwrap(), 1);
+/// assert!(std::fs::read_to_string(&output).unwrap().contains(">sp|P1_2|B"));
+/// ```
+pub fn uniquify_ids(
+    input: &Path,
+    output: &Path,
+    separator: &str,
+    id_index: usize,
+) -> io::Result<usize> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut renamed = 0;
+    map_entries(input, output, |mut entry| {
+        let id_range = seq_id_range(&entry.description, separator, id_index);
+        let id = &entry.description[id_range.clone()];
+        let count = seen.entry(id.to_string()).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            let mut n = *count;
+            let mut unique = format!("{}_{}", id, n);
+            while seen.contains_key(&unique) {
+                n += 1;
+                unique = format!("{}_{}", id, n);
+            }
+            seen.insert(id.to_string(), n);
+            entry
+                .description
+                .insert_str(id_range.end, &unique[id_range.len()..]);
+            seen.insert(unique, 1);
+            renamed += 1;
+        }
+        Some(entry)
+    })?;
+    Ok(renamed)
+}
+
+/// Writes the entries of an indexed file in the order given by `id_order`.
+///
+/// Each entry is read by seeking to its offset, so only one entry is held
+/// in memory at a time. Returns the number of entries written and the ids
+/// that were not found in the index.
+pub fn reorder(
+    fasta: &Path,
+    index: &FastaIndex,
+    id_order: &[String],
+    output: &Path,
+) -> io::Result<(usize, Vec<String>)> {
+    let mut writer = FastaWriter::new(output)?;
+    let mut written = 0;
+    let mut missing = Vec::new();
+    for id in id_order {
+        match index.id_to_offset.get(id) {
+            Some(offset) => {
+                let entry = FastaEntry::from_index(fasta, *offset)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                writer.write_fasta_entry(&entry)?;
+                written += 1;
+            }
+            None => missing.push(id.clone()),
+        }
+    }
+    writer.finish()?;
+    Ok((written, missing))
+}
+
+/// Writes the entries of an indexed file into one file per group,
+/// `output_dir/{group}.fasta`, in the order of the group's ids.
+///
+/// Each entry is read by seeking to its offset, like in `reorder`, so the
+/// file does not need to be grouped. It is an error if an id is not in the
+/// index or a group name is not a valid file name; both are checked before
+/// anything is written.
+///
+/// # Examples
+/// ```
+/// use fasta::index::FastaIndex;
+/// use fasta::read::write_grouped;
+/// use std::path::Path;
+///
+/// let fasta = Path::new("./resources/test.fasta");
+/// let index = FastaIndex::new(fasta, "|", 1);
+/// let outdir = std::env::temp_dir().join("fasta_write_grouped_doctest");
+/// std::fs::create_dir_all(&outdir).unwrap();
+/// let groups = vec![
+///     ("mammals".to_string(), vec!["Q2HZH0".to_string(), "H0VS30".to_string()]),
+///     ("plants".to_string(), vec!["P93158".to_string()]),
+/// ];
+/// write_grouped(fasta, &index, &groups, &outdir).unwrap();
+/// assert!(outdir.join("plants.fasta").exists());
+/// ```
+pub fn write_grouped(
+    fasta: &Path,
+    index: &FastaIndex,
+    groups: &[(String, Vec<String>)],
+    output_dir: &Path,
+) -> io::Result<()> {
+    let mut paths = Vec::with_capacity(groups.len());
+    for (group, ids) in groups {
+        if let Some(id) = ids.iter().find(|id| !index.id_to_offset.contains_key(*id)) {
+            return Err(FastaError::parse(
+                ErrorKind::IdNotFound,
+                format!("Id of group {:?} not found in index: {:?}", group, id),
+            )
+            .into());
+        }
+        paths.push(group_path(output_dir, group)?);
+    }
+    for ((_, ids), path) in groups.iter().zip(paths) {
+        reorder(fasta, index, ids, &path)?;
+    }
+    Ok(())
+}
+
+/// Writes a copy of an indexed file with the sequence of entry `id` replaced.
+///
+/// Everything but the old sequence lines of the entry is copied verbatim,
+/// including its description line and its `;` comment lines, which are
+/// written before the new sequence. The new sequence is wrapped like the
+/// rest of the file: at the length of the first sequence line that is
+/// followed by another one, or not at all if there is none. Line breaks
+/// match those of the description line. `output` has to differ from `fasta`.
+pub fn replace_sequence(
+    fasta: &Path,
+    index: &FastaIndex,
+    id: &str,
+    new_sequence: &str,
+    output: &Path,
+) -> Result<(), FastaError> {
+    let offset = *index.id_to_offset.get(id).ok_or_else(|| {
+        FastaError::parse(
+            ErrorKind::IdNotFound,
+            format!("No entry for id {:?} in index.", id),
+        )
+    })?;
+    let width = detect_line_width(fasta)?;
+
+    let mut reader = BufReader::new(File::open(fasta)?);
+    let mut writer = BufWriter::new(File::create(output)?);
+    io::copy(&mut reader.by_ref().take(offset), &mut writer)?;
+
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    if !line.starts_with(b">") {
+        return Err(FastaError::parse(
+            ErrorKind::IndexNotAtDescription,
+            format!(
+                "No description line found at offset {} for {:?}.",
+                offset, id
+            ),
+        ));
+    }
+    writer.write_all(&line)?;
+    let line_end: &[u8] = if line.ends_with(b"\r\n") {
+        b"\r\n"
+    } else {
+        b"\n"
+    };
+
+    // skip the old sequence, up to the next description or blank line
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0
+            || line.starts_with(b">")
+            || line.iter().all(u8::is_ascii_whitespace)
+        {
+            break;
+        }
+        if line.starts_with(b";") {
+            writer.write_all(&line)?;
+            if !line.ends_with(b"\n") {
+                writer.write_all(line_end)?;
+            }
+        }
+    }
+
+    let seq = new_sequence.as_bytes();
+    let chunk_size = if width == 0 { seq.len().max(1) } else { width };
+    for seq_line in seq.chunks(chunk_size) {
+        writer.write_all(seq_line)?;
+        writer.write_all(line_end)?;
+    }
+    writer.write_all(&line)?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Finds the length of the first sequence line followed by another sequence
+// line, i.e. the line width of a wrapped file, or 0 if no sequence is wrapped.
+fn detect_line_width(path: &Path) -> io::Result<usize> {
+    let mut previous: Option<usize> = None;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.starts_with(';') {
+            continue;
+        } else if line.is_empty() || line.starts_with('>') {
+            previous = None;
+        } else if let Some(width) = previous {
+            return Ok(width);
+        } else {
+            previous = Some(line.len());
+        }
+    }
+    Ok(0)
+}
+
+/// The normalizations applied by `canonicalize`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanonicalizeOptions {
+    /// Remove whitespace from sequences and trailing whitespace from descriptions.
+    pub strip_whitespace: bool,
+    /// Convert sequences to upper case.
+    pub uppercase: bool,
+    /// Remove trailing stop symbols (`*`) from sequences.
+    pub strip_stops: bool,
+    /// Sort entries by id, the first word of the description.
+    pub sort_by_id: bool,
+    /// The line width sequences are wrapped to; 0 disables wrapping.
+    pub width: usize,
+}
+
+impl Default for CanonicalizeOptions {
+    fn default() -> Self {
+        CanonicalizeOptions {
+            strip_whitespace: true,
+            uppercase: true,
+            strip_stops: true,
+            sort_by_id: true,
+            width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+/// Writes a normalized copy of a FASTA file, e.g. to make references reproducible.
+///
+/// Each normalization is toggled in `opts`. Without sorting, entries are
+/// streamed one at a time. Sorting holds all entries in memory; for files
+/// that do not fit, sort the ids of an index and pass them to `reorder`
+/// instead. The sort is stable, so entries with the same id keep their
+/// order. Returns the number of entries written.
+///
+/// # Examples
+/// ```
+/// use fasta::read::{canonicalize, CanonicalizeOptions};
+/// use std::path::Path;
+///
+/// let output = std::env::temp_dir().join("canonical.fasta");
+/// let opts = CanonicalizeOptions {
+///     uppercase: false,
+///     ..Default::default()
+/// };
+/// assert_eq!(canonicalize(Path::new("./resources/test.fasta"), &output, opts).unwrap(), 3);
+/// ```
+pub fn canonicalize(input: &Path, output: &Path, opts: CanonicalizeOptions) -> io::Result<usize> {
+    let normalize = |mut entry: FastaEntry| {
+        if opts.strip_whitespace {
+            entry
+                .description
+                .truncate(entry.description.trim_end().len());
+            entry.sequence.retain(|c| !c.is_whitespace());
+        }
+        if opts.uppercase {
+            entry.sequence.make_ascii_uppercase();
+        }
+        if opts.strip_stops {
+            entry
+                .sequence
+                .truncate(entry.sequence.trim_end_matches('*').len());
+        }
+        entry
+    };
+    let mut entries: Box<dyn Iterator<Item = Result<FastaEntry, FastaError>>> = Box::new(
+        FastaReader::try_new(input)?.map(|record| record.map(FastaEntry::from).map(normalize)),
+    );
+    if opts.sort_by_id {
+        let mut sorted = entries.collect::<Result<Vec<_>, _>>()?;
+        sorted.sort_by(|a, b| {
+            seq_id_from_description(&a.description, " ", 0).cmp(seq_id_from_description(
+                &b.description,
+                " ",
+                0,
+            ))
+        });
+        entries = Box::new(sorted.into_iter().map(Ok));
+    }
+
+    let mut writer = FastaWriter::with_width(output, opts.width)?;
+    let mut written = 0;
+    for entry in entries {
+        let entry = entry?;
+        writer.write_fasta_entry(&entry)?;
+        written += 1;
+    }
+    writer.finish()?;
+    Ok(written)
+}
+
+/// The differences between two versions of a FASTA file.
+#[derive(Debug, Default, PartialEq)]
+pub struct FastaDiff {
+    /// Ids only found in the new file, in file order.
+    pub added: Vec<String>,
+    /// Ids only found in the old file, in file order.
+    pub removed: Vec<String>,
+    /// Ids found in both files with different sequences, in new file order.
+    pub changed: Vec<String>,
+}
+
+/// Compares the entries of two FASTA files by id.
+///
+/// Only the length and a hash per sequence of the old file are kept in
+/// memory, not the sequences themselves. Ids are parsed as in `FastaIndex::new`;
+/// if an id occurs more than once in the old file, its last entry is compared.
+///
+/// Sequences are compared by their 64-bit hash ignoring case, like in
+/// `content_digest`, so changes of case alone, e.g. of soft-masking, are
+/// not reported. Two sequences of the same length with the same hash are
+/// taken to be equal; such collisions are very unlikely, but would hide
+/// a change.
+pub fn diff(old: &Path, new: &Path, separator: &str, id_index: usize) -> io::Result<FastaDiff> {
+    let mut old_ids = Vec::new();
+    let mut old_hashes: HashMap<String, ((usize, u64), bool)> = HashMap::new();
+    for record in FastaReader::try_new(old)? {
+        let [description, seq] = record?;
+        let id = seq_id_from_description(&description, separator, id_index).to_string();
+        let key = (seq.len(), sequence_hash(&seq));
+        if old_hashes.insert(id.clone(), (key, false)).is_none() {
+            old_ids.push(id);
+        }
+    }
+
+    let mut res = FastaDiff::default();
+    for record in FastaReader::try_new(new)? {
+        let [description, seq] = record?;
+        let id = seq_id_from_description(&description, separator, id_index);
+        match old_hashes.get_mut(id) {
+            Some((key, seen)) => {
+                *seen = true;
+                if *key != (seq.len(), sequence_hash(&seq)) {
+                    res.changed.push(id.to_string());
+                }
+            }
+            None => res.added.push(id.to_string()),
+        }
+    }
+    res.removed = old_ids.into_iter().filter(|id| !old_hashes[id].1).collect();
+    Ok(res)
+}
+
+/// Counts the ids that occur more than once in a FASTA file.
+///
+/// Returns only the duplicated ids with their number of occurrences, so an
+/// empty map means the file can be indexed. To bound memory, the file is
+/// read twice: the first pass keeps a 64-bit hash per id to find candidate
+/// duplicates, the second counts the ids of the candidates exactly.
+/// Ids are parsed as in `FastaIndex::new`.
+pub fn id_duplicates(
+    path: &Path,
+    separator: &str,
+    id_index: usize,
+) -> io::Result<HashMap<String, usize>> {
+    let id_hash = |id: &str| {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let mut seen = HashSet::new();
+    let mut candidates = HashSet::new();
+    for record in FastaReader::try_new(path)? {
+        let [description, _] = record?;
+        let hash = id_hash(seq_id_from_description(&description, separator, id_index));
+        if !seen.insert(hash) {
+            candidates.insert(hash);
+        }
+    }
+    drop(seen);
+
+    let mut counts = HashMap::new();
+    if !candidates.is_empty() {
+        for record in FastaReader::try_new(path)? {
+            let [description, _] = record?;
+            let id = seq_id_from_description(&description, separator, id_index);
+            if candidates.contains(&id_hash(id)) {
+                *counts.entry(id.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+    counts.retain(|_, count| *count > 1);
+    Ok(counts)
+}
+
+/// Splits `query` into the ids that occur in a FASTA file and those that don't.
+///
+/// Returns `(present, absent)`, each in query order. Only the query ids are
+/// kept in memory while the description lines of the file are read once,
+/// skipping sequences like `FastaAccessions::from_fasta_bytes`. Ids are
+/// parsed as in `FastaIndex::new`.
+///
+/// # Examples
+/// ```
+/// use fasta::read::id_presence;
+/// use std::path::Path;
+///
+/// let query = vec!["P93158".to_string(), "Q00000".to_string()];
+/// let (present, absent) =
+///     id_presence(Path::new("./resources/test.fasta"), &query, "|", 1).unwrap();
+/// assert_eq!(present, vec!["P93158"]);
+/// assert_eq!(absent, vec!["Q00000"]);
+/// ```
+pub fn id_presence(
+    path: &Path,
+    query: &[String],
+    separator: &str,
+    id_index: usize,
+) -> io::Result<(Vec<String>, Vec<String>)> {
+    let mut missing = query.iter().map(String::as_str).collect::<HashSet<_>>();
+    if !missing.is_empty() {
+        scan_descriptions(path, |description| {
+            missing.remove(seq_id_from_description(description, separator, id_index));
+            Ok(!missing.is_empty())
+        })?;
+    }
+    Ok(query
+        .iter()
+        .cloned()
+        .partition(|id| !missing.contains(id.as_str())))
+}
+
+/// Checks whether the ids of a FASTA file are in non-decreasing order.
+///
+/// Ids are compared as strings (`str::cmp`) and parsed as in `FastaIndex::new`.
+/// Only description lines are read, skipping sequences like
+/// `FastaAccessions::from_fasta_bytes`, and reading stops at the first id
+/// smaller than its predecessor. Use this to check the precondition of
+/// `merge_sorted` up front.
+pub fn is_sorted_by_id(path: &Path, separator: &str, id_index: usize) -> io::Result<bool> {
+    let mut previous: Option<String> = None;
+    let mut sorted = true;
+    scan_descriptions(path, |description| {
+        let id = seq_id_from_description(description, separator, id_index);
+        sorted = previous.as_deref().map_or(true, |previous| previous <= id);
+        previous = Some(id.to_string());
+        Ok(sorted)
+    })?;
+    Ok(sorted)
+}
+
+/// An item of `merge_sorted`.
+#[derive(Debug, PartialEq)]
+pub enum MergeItem {
+    /// An entry whose id is only in the first file.
+    OnlyA(FastaEntry),
+    /// An entry whose id is only in the second file.
+    OnlyB(FastaEntry),
+    /// The entries of both files with the same id.
+    Both(FastaEntry, FastaEntry),
+}
+
+// One input of `merge_sorted`, with its current entry.
+struct SortedInput {
+    reader: FastaReader,
+    head: Option<(String, FastaEntry)>,
+    separator: String,
+    id_index: usize,
+}
+
+impl SortedInput {
+    fn open(path: &Path, separator: &str, id_index: usize) -> Result<Self, FastaError> {
+        let mut res = SortedInput {
+            reader: FastaReader::try_new(path)?,
+            head: None,
+            separator: separator.to_string(),
+            id_index,
+        };
+        res.head = res.read()?;
+        Ok(res)
+    }
+
+    fn read(&mut self) -> Result<Option<(String, FastaEntry)>, FastaError> {
+        match self.reader.read_record() {
+            Some(record) => {
+                let entry = FastaEntry::from(record?);
+                let id =
+                    seq_id_from_description(&entry.description, &self.separator, self.id_index)
+                        .to_string();
+                Ok(Some((id, entry)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Returns the current entry and reads the next one, checking the order.
+    fn advance(&mut self) -> Result<FastaEntry, FastaError> {
+        let next = self.read()?;
+        let (id, entry) = std::mem::replace(&mut self.head, next).unwrap();
+        if let Some((next_id, _)) = &self.head {
+            if *next_id == id {
+                return Err(FastaError::parse(
+                    ErrorKind::DuplicateId,
+                    format!("Multiple entries found for id: {:?}", id),
+                ));
+            } else if *next_id < id {
+                return Err(FastaError::parse(
+                    ErrorKind::NotSorted,
+                    format!(
+                        "Entries not sorted by id: {:?} after {:?}; see `is_sorted_by_id`.",
+                        next_id, id
+                    ),
+                ));
+            }
+        }
+        Ok(entry)
+    }
+}
+
+/// Merges two FASTA files sorted by id, advancing both in lockstep.
+///
+/// Entries with the same id in both files are yielded together, all other
+/// entries on their own, in ascending id order. Only one entry per file is
+/// held in memory. Both files have to be sorted by id (in byte order of the
+/// ids, as by `str::cmp`) without duplicate ids; otherwise an error with
+/// `ErrorKind::NotSorted` or `ErrorKind::DuplicateId` is yielded, after which
+/// the iterator stops. Ids are parsed as in `FastaIndex::new`.
+///
+/// # Examples
+/// ```
+/// use fasta::read::{merge_sorted, MergeItem};
+/// use std::path::Path;
+///
+/// let a = Path::new("./resources/test_reordered.fasta");
+/// for item in merge_sorted(a, a, "|", 1).unwrap() {
+///     assert!(matches!(item.unwrap(), MergeItem::Both(_, _)));
+/// }
+/// ```
+pub fn merge_sorted(
+    a: &Path,
+    b: &Path,
+    separator: &str,
+    id_index: usize,
+) -> Result<impl Iterator<Item = Result<MergeItem, FastaError>>, FastaError> {
+    let mut a = SortedInput::open(a, separator, id_index)?;
+    let mut b = SortedInput::open(b, separator, id_index)?;
+    let mut failed = false;
+    Ok(std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let item = match (&a.head, &b.head) {
+            (None, None) => return None,
+            (Some(_), None) => a.advance().map(MergeItem::OnlyA),
+            (None, Some(_)) => b.advance().map(MergeItem::OnlyB),
+            (Some((id_a, _)), Some((id_b, _))) => match id_a.cmp(id_b) {
+                std::cmp::Ordering::Less => a.advance().map(MergeItem::OnlyA),
+                std::cmp::Ordering::Greater => b.advance().map(MergeItem::OnlyB),
+                std::cmp::Ordering::Equal => a
+                    .advance()
+                    .and_then(|entry_a| Ok(MergeItem::Both(entry_a, b.advance()?))),
+            },
+        };
+        failed = item.is_err();
+        Some(item)
+    }))
+}
+
+/// Rewrites the id of every entry by a regex substitution.
+///
+/// The id is the first whitespace-delimited field of the description,
+/// without the leading `>`; the rest of the description is kept as is.
+/// `replacement` may refer to capture groups as in `Regex::replace`.
+/// Returns the number of entries whose id was changed.
+///
+/// # Examples
+/// Strip version suffixes from accessions:
+/// ```
+/// use fasta::read::{rename_by_regex, FastaReader};
+/// use regex::Regex;
+///
+/// let inpath = std::env::temp_dir().join("fasta_rename_doctest_in.fasta");
+/// let outpath = std::env::temp_dir().join("fasta_rename_doctest.fasta");
+/// std::fs::write(&inpath, ">NM_000546.6 TP53\nACGT\n>NM_001126\nGG\n>XM_1.2\nTT\n").unwrap();
+/// let version = Regex::new(r"\.\d+$").unwrap();
+/// assert_eq!(rename_by_regex(&inpath, &outpath, &version, "").unwrap(), 2);
+/// let descriptions = FastaReader::new(&outpath)
+///     .map(|record| record.unwrap()[0].clone())
+///     .collect::<Vec<_>>();
+/// assert_eq!(descriptions, [">NM_000546 TP53", ">NM_001126", ">XM_1"]);
+/// ```
+#[cfg(feature = "regex")]
+pub fn rename_by_regex(
+    input: &Path,
+    output: &Path,
+    pattern: &Regex,
+    replacement: &str,
+) -> io::Result<usize> {
+    let mut writer = FastaWriter::new(output)?;
+    let mut renamed = 0;
+    for record in FastaReader::try_new(input)? {
+        let [description, seq] = record?;
+        let line = &description[1..];
+        let (id, rest) = match line.find(char::is_whitespace) {
+            Some(i) => line.split_at(i),
+            None => (line, ""),
+        };
+        let new_id = pattern.replace(id, replacement);
+        if new_id != id {
+            renamed += 1;
+        }
+        writer.write_entry(&format!("{}{}", new_id, rest), &seq)?;
+    }
+    writer.finish()?;
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::FastaAccessions;
+
+    #[test]
+    fn read_errors_are_yielded() {
+        let path = std::env::temp_dir().join("fasta_rs_invalid_utf8.fasta");
+        fs::write(&path, b">a\nAC\n>b\nG\xffG\n>c\nTT\n").unwrap();
+        let mut reader = FastaReader::new(&path);
+        assert_eq!(reader.next().unwrap().unwrap()[1], "AC");
+        match reader.next() {
+            Some(Err(FastaError::Io(e))) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            other => panic!("expected InvalidData error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reader_construction_errors_name_the_file() {
+        let missing = Path::new("./resources/does_not_exist.fasta");
+        match FastaReader::try_new(missing) {
+            Err(FastaError::Io(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+                assert!(e.to_string().contains("does_not_exist.fasta"));
+            }
+            _ => panic!("expected NotFound error"),
+        }
+        let path = std::env::temp_dir().join("fasta_rs_no_description.fasta");
+        fs::write(&path, "\nACGT\n>a\nGG\n").unwrap();
+        match FastaReader::try_new(&path) {
+            Err(FastaError::Parse { kind, message }) => {
+                assert_eq!(kind, ErrorKind::NoDescription);
+                assert!(message.contains("fasta_rs_no_description.fasta"));
+                assert!(message.contains("ACGT"));
+            }
+            _ => panic!("expected NoDescription error"),
+        }
+    }
+
+    #[test]
+    fn charset_of_messy_file() {
+        let path = std::env::temp_dir().join("fasta_rs_charset_audit.fasta");
+        fs::write(&path, b">a \xc3\xa9t\xc3\xa9\r\nAC\0G\r\nac\xff\n>b\nNN\n").unwrap();
+        let report = charset_audit(&path).unwrap();
+        let counts = [
+            ('A', 1),
+            ('C', 1),
+            ('G', 1),
+            ('a', 1),
+            ('c', 1),
+            ('N', 2),
+            ('\0', 1),
+        ];
+        let mut expected = counts.iter().cloned().collect::<HashMap<_, _>>();
+        expected.insert(char::REPLACEMENT_CHARACTER, 1);
+        assert_eq!(report.sequence_chars, expected);
+        assert_eq!(report.control_chars, 1);
+        assert_eq!(report.non_ascii_chars, 3);
+        assert_eq!(report.invalid_utf8_lines, 1);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn grouped_via_index() {
+        let outdir = std::env::temp_dir().join("fasta_rs_write_grouped");
+        fs::create_dir_all(&outdir).unwrap();
+        let fasta = outdir.join("input.fasta");
+        fs::write(&fasta, ">a\nAC\n>b\nGG\n>c\nTT\n").unwrap();
+        let index = FastaIndex::new(&fasta, " ", 0);
+        let group = |name: &str, ids: &[&str]| {
+            (
+                name.to_string(),
+                ids.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+            )
+        };
+        let groups = vec![group("x", &["c", "a"]), group("y", &["b"])];
+        write_grouped(&fasta, &index, &groups, &outdir).unwrap();
+        assert_eq!(
+            fs::read_to_string(outdir.join("x.fasta")).unwrap(),
+            ">c\nTT\n>a\nAC\n"
+        );
+        assert_eq!(
+            fs::read_to_string(outdir.join("y.fasta")).unwrap(),
+            ">b\nGG\n"
+        );
+        let missing = vec![group("z", &["a"]), group("w", &["d"])];
+        assert!(write_grouped(&fasta, &index, &missing, &outdir).is_err());
+        assert!(!outdir.join("z.fasta").exists());
+    }
+
+    #[test]
+    fn query_ids_present_and_absent() {
+        let path = std::env::temp_dir().join("fasta_rs_id_presence.fasta");
+        fs::write(&path, ">a x\nAC\n>b\nGG\n>c\nTT\n").unwrap();
+        let query = ["c", "z", "a", "y", "c"].map(String::from);
+        let (present, absent) = id_presence(&path, &query, " ", 0).unwrap();
+        assert_eq!(present, vec!["c", "a", "c"]);
+        assert_eq!(absent, vec!["z", "y"]);
+        assert_eq!(id_presence(&path, &[], " ", 0).unwrap(), (vec![], vec![]));
+
+        // sequences are not decoded
+        fs::write(&path, b">a\nAC\xff\n>b\nGG\n").unwrap();
+        let (present, _) = id_presence(&path, &query, " ", 0).unwrap();
+        assert_eq!(present, vec!["a"]);
+    }
+
+    #[test]
+    fn characters_substituted_in_sequences() {
+        let input = std::env::temp_dir().join("fasta_rs_substitute_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_substitute_out.fasta");
+        fs::write(&input, ">U.1 uracil\nACGU..U*\n").unwrap();
+        let table = [('U', 'T'), ('.', '-'), ('T', 'A'), ('U', 'N')];
+        assert_eq!(substitute(&input, &output, &table).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">U.1 uracil\nACGT--T*\n"
+        );
+    }
+
+    #[test]
+    fn last_entry_searched_backwards() {
+        let path = std::env::temp_dir().join("fasta_rs_last_entry.fasta");
+        let content = ">a\nA>CG\n>b x\nGG\nTT\n";
+        fs::write(&path, content).unwrap();
+        let last = last_entry(&path).unwrap().unwrap();
+        assert_eq!(
+            (last.description.as_str(), last.sequence.as_str()),
+            (">b x", "GGTT")
+        );
+        // descriptions found across chunk boundaries
+        for chunk_size in 1..content.len() as u64 {
+            let mut file = File::open(&path).unwrap();
+            assert_eq!(
+                last_description_offset(&mut file, chunk_size).unwrap(),
+                Some(8)
+            );
+        }
+        assert_eq!(
+            last_entry(Path::new("./resources/test.fasta.gz")).unwrap(),
+            last_entry(Path::new("./resources/test.fasta")).unwrap()
+        );
+        fs::write(&path, "").unwrap();
+        assert_eq!(last_entry(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn low_complexity_ids() {
+        let path = std::env::temp_dir().join("fasta_rs_low_complexity.fasta");
+        let repeat = "AT".repeat(300);
+        fs::write(&path, format!(">rep x\n{}\n>b\nACGT\n", repeat)).unwrap();
+        assert_eq!(
+            low_complexity_ids_by_compression(&path, 0.2).unwrap(),
+            vec!["rep"]
+        );
+    }
+
+    #[test]
+    fn sequences_trimmed_by_coords() {
+        let input = std::env::temp_dir().join("fasta_rs_trim_coords_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_trim_coords_out.fasta");
+        fs::write(&input, ">a signal\nMKKLLAC\n>b\nGGTT\n>c\nACGT\n").unwrap();
+        let mut coords = HashMap::new();
+        coords.insert("a".to_string(), (3, 7));
+        coords.insert("c".to_string(), (2, 10));
+        assert_eq!(trim_by_coords(&input, &coords, &output, true).unwrap(), 3);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a signal\nLLAC\n>b\nGGTT\n>c\nGT\n"
+        );
+        assert_eq!(trim_by_coords(&input, &coords, &output, false).unwrap(), 2);
+    }
+
+    #[test]
+    fn terminators_are_normalized() {
+        let input = std::env::temp_dir().join("fasta_rs_mixed_terminators.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_normalized_terminators.fasta");
+        fs::write(
+            &input,
+            "NN\r\n>a\r\nAC\r\nGT\r\n\r\n\n>b\nGG\n>c x\r\nTT\r\n\r\nCCC\nA\n",
+        )
+        .unwrap();
+        // sequences without description are entries of the lenient reader
+        assert_eq!(normalize_terminators(&input, &output).unwrap(), 5);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">unnamed_1\nNN\n>a\nAC\nGT\n>b\nGG\n>c x\nTT\n>unnamed_2\nCCC\nA\n"
+        );
+        let strict = FastaReader::try_new(&output)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(strict.len(), 5);
+        assert_eq!(strict[4], [">unnamed_2".to_string(), "CCCA".to_string()]);
+    }
+
+    #[test]
+    fn partition_reopens_closed_files() {
+        let outdir = std::env::temp_dir().join("fasta_rs_partition");
+        fs::create_dir_all(&outdir).unwrap();
+        let input = outdir.join("input.fasta");
+        fs::write(&input, ">a1\nAC\n>b1\nGG\n>c1\nTT\n>a2\nCC\n>b2\nAA\n").unwrap();
+        let group = |entry: &FastaEntry| entry.description[1..2].to_string();
+        let counts = partition_with(&input, &outdir, group, 2).unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!((counts["a"], counts["b"], counts["c"]), (2, 2, 1));
+        assert_eq!(
+            fs::read_to_string(outdir.join("a.fasta")).unwrap(),
+            ">a1\nAC\n>a2\nCC\n"
+        );
+        assert_eq!(
+            fs::read_to_string(outdir.join("b.fasta")).unwrap(),
+            ">b1\nGG\n>b2\nAA\n"
+        );
+        let invalid = partition(&input, &outdir, |_| "x/y".to_string());
+        assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn empty_and_header_only_files() {
+        use crate::map::FastaMap;
+        use crate::pieces::FastaLengths;
+
+        let empty = std::env::temp_dir().join("fasta_rs_empty.fasta");
+        fs::write(&empty, "\n").unwrap();
+        assert_eq!(FastaReader::new(&empty).map(Result::unwrap).count(), 0);
+        assert!(FastaIndex::new(&empty, " ", 0).id_to_offset.is_empty());
+        assert!(FastaMap::from_fasta(&empty).id_to_seq.is_empty());
+        assert!(FastaAccessions::from_fasta(&empty, " ", 0)
+            .accessions
+            .is_empty());
+        assert!(FastaLengths::from_fasta(&empty, " ", 0)
+            .sequence_lengths
+            .is_empty());
+
+        let headers = std::env::temp_dir().join("fasta_rs_headers_only.fasta");
+        fs::write(&headers, ">a\n>b\n").unwrap();
+        let entries = FastaReader::new(&headers)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                [">a".to_string(), String::new()],
+                [">b".to_string(), String::new()]
+            ]
+        );
+        assert_eq!(FastaIndex::new(&headers, " ", 0).id_to_offset.len(), 2);
+        assert_eq!(FastaMap::from_fasta(&headers).id_to_seq[">b"], "");
+        assert_eq!(
+            FastaAccessions::from_fasta(&headers, " ", 0).accessions,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            FastaLengths::from_fasta(&headers, " ", 0).sequence_lengths["b"],
+            0
+        );
+
+        let headless = std::env::temp_dir().join("fasta_rs_headless.fasta");
+        fs::write(&headless, "ACGT\n").unwrap();
+        assert!(FastaReader::try_new(&headless).is_err());
+    }
+
+    #[test]
+    fn filter_by_predicate() {
+        let outpath = std::env::temp_dir().join("fasta_rs_filter_predicate.fasta");
+        let input = Path::new("./resources/test.fasta");
+        let written = filter(input, &outpath, |entry| entry.sequence.len() > 150).unwrap();
+        let expected = read_all(input)
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.sequence.len() > 150)
+            .collect::<Vec<_>>();
+        assert_eq!(written, expected.len());
+        assert_eq!(read_all(&outpath).unwrap(), expected);
+        assert_eq!(filter(input, &outpath, |_| false).unwrap(), 0);
+        assert_eq!(std::fs::metadata(&outpath).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn roundtrip_is_verified() {
+        let input = Path::new("./resources/test.fasta");
+        assert!(verify_roundtrip(input, WriteOptions::default()).unwrap());
+        let unwrapped = WriteOptions {
+            width: 0,
+            record_separator: Some(String::new()),
+            ..WriteOptions::default()
+        };
+        assert!(verify_roundtrip(input, unwrapped).unwrap());
+        let separated = WriteOptions {
+            record_separator: Some("//".to_string()),
+            ..WriteOptions::default()
+        };
+        assert!(!verify_roundtrip(input, separated).unwrap());
+    }
+
+    #[test]
+    fn content_digest_ignores_order_and_format() {
+        let digest = content_digest(Path::new("./resources/test.fasta")).unwrap();
+        assert_eq!(
+            digest,
+            content_digest(Path::new("./resources/test_reordered.fasta")).unwrap()
+        );
+        assert_eq!(
+            digest,
+            content_digest(Path::new("./resources/test_short_descr.fasta")).unwrap()
+        );
+    }
+
+    #[test]
+    fn entries_from_spawned_reader() {
+        let entries = spawn_reader(Path::new("./resources/test.fasta"), 1)
+            .into_iter()
+            .collect::<Result<Vec<FastaEntry>, FastaError>>()
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[1].description.starts_with(">tr|P93158|"));
+        assert_eq!(entries[2].sequence.len(), 180);
+
+        let mut failed = spawn_reader(Path::new("./resources/missing.fasta"), 1).into_iter();
+        assert!(matches!(failed.next(), Some(Err(FastaError::Io(_)))));
+        assert!(failed.next().is_none());
+    }
+
+    #[test]
+    fn byte_ranges_of_records() {
+        let ranges = record_ranges(Path::new("./resources/test.fasta"), "|", 1).unwrap();
+        assert_eq!(
+            ranges,
+            vec![
+                ("Q2HZH0".to_string(), 0, 206),
+                ("P93158".to_string(), 206, 422),
+                ("H0VS30".to_string(), 422, 718),
+            ]
+        );
+    }
+
+    #[test]
+    fn appended_entries_are_indexed() {
+        let fasta = std::env::temp_dir().join("fasta_rs_append_entries.fasta");
+        let index = std::env::temp_dir().join("fasta_rs_append_entries.index");
+        // drop the trailing newline to check that appending restores it
+        let content = fs::read_to_string("./resources/test.fasta").unwrap();
+        fs::write(&fasta, content.trim_end()).unwrap();
+        FastaIndex::new(&fasta, "|", 1).to_json(&index).unwrap();
+
+        let entry = FastaEntry {
+            description: "sp|P01584|IL1B_HUMAN Interleukin-1 beta".to_string(),
+            sequence: "MAEVPELASEMMAYYSGNEDDLFFEADGPKQMKCSFQDLDLCPLDGGIQLRISDHHYSKG".to_string(),
+        };
+        append_entries(&fasta, &index, vec![entry].into_iter(), "|", 1).unwrap();
+        let fasta_index = FastaIndex::from_json(&index).unwrap();
+        let appended = FastaEntry::from_index(&fasta, fasta_index.id_to_offset["P01584"]).unwrap();
+        assert_eq!(
+            appended.description,
+            ">sp|P01584|IL1B_HUMAN Interleukin-1 beta"
+        );
+        assert_eq!(FastaReader::new(&fasta).map(Result::unwrap).count(), 4);
+
+        // duplicates roll back the whole append
+        let len = fs::metadata(&fasta).unwrap().len();
+        let duplicate = FastaEntry {
+            description: ">tr|P93158|dup".to_string(),
+            sequence: "ACGT".to_string(),
+        };
+        assert!(append_entries(&fasta, &index, vec![duplicate].into_iter(), "|", 1).is_err());
+        assert_eq!(fs::metadata(&fasta).unwrap().len(), len);
+        assert_eq!(FastaIndex::from_json(&index).unwrap(), fasta_index);
+
+        // so do ids that no index constructor accepts
+        for (description, id_index, kind) in [
+            (">sp|A3|x", 5, ErrorKind::IdIndexOutOfRange),
+            (">sp||empty", 1, ErrorKind::EmptyId),
+        ] {
+            let entry = FastaEntry {
+                description: description.to_string(),
+                sequence: "ACGT".to_string(),
+            };
+            match append_entries(&fasta, &index, vec![entry].into_iter(), "|", id_index) {
+                Err(FastaError::Parse { kind: k, .. }) => assert_eq!(k, kind),
+                other => panic!("expected {:?} error, got {:?}", kind, other),
+            }
+            assert_eq!(fs::metadata(&fasta).unwrap().len(), len);
+        }
+    }
+
+    #[test]
+    fn appended_entries_keep_lengths() {
+        let fasta = std::env::temp_dir().join("fasta_rs_append_lengths.fasta");
+        let index = std::env::temp_dir().join("fasta_rs_append_lengths.index");
+        fs::copy("./resources/test.fasta", &fasta).unwrap();
+        FastaIndex::new_with_lengths(&fasta, "|", 1)
+            .unwrap()
+            .to_json(&index)
+            .unwrap();
+
+        let entries = vec![
+            FastaEntry {
+                description: ">sp|A1|long".to_string(),
+                sequence: "ACGT".repeat(40),
+            },
+            FastaEntry {
+                description: ">sp|A2|short".to_string(),
+                sequence: "GATTACA".to_string(),
+            },
+        ];
+        append_entries(&fasta, &index, entries.into_iter(), "|", 1).unwrap();
+        let appended = FastaIndex::from_json(&index).unwrap();
+        assert_eq!(appended.length("A1"), Some(160));
+        assert_eq!(
+            appended,
+            FastaIndex::new_with_lengths(&fasta, "|", 1).unwrap()
+        );
+        appended.to_fai(&fasta.with_extension("fai")).unwrap();
+    }
+
+    #[test]
+    fn rna_file_normalized_to_dna() {
+        let rna = std::env::temp_dir().join("fasta_rs_normalize_rna.fasta");
+        let dna = std::env::temp_dir().join("fasta_rs_normalize_dna.fasta");
+        fs::write(&rna, ">r1\nACGU\nuuGA\n>r2\nGGCC\n").unwrap();
+        assert_eq!(normalize_to_dna(&rna, &dna).unwrap(), 2);
+        let seqs = FastaReader::new(&dna)
+            .map(Result::unwrap)
+            .map(|[_, s]| s)
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec!["ACGTttGA", "GGCC"]);
+    }
+
+    #[test]
+    fn file_through_pipeline() {
+        let outpath = std::env::temp_dir().join("fasta_rs_apply_pipeline.fasta");
+        let mut pipeline = TransformPipeline::new();
+        pipeline.push(crate::transform::Uppercase);
+        assert_eq!(
+            apply_pipeline(
+                Path::new("./resources/test_reordered.fasta"),
+                &outpath,
+                &pipeline
+            )
+            .unwrap(),
+            3
+        );
+        assert_eq!(
+            content_digest(&outpath).unwrap(),
+            content_digest(Path::new("./resources/test.fasta")).unwrap()
+        );
+        assert!(FastaReader::new(&outpath)
+            .map(Result::unwrap)
+            .all(|[_, s]| s == s.to_uppercase()));
+    }
+
+    #[test]
+    fn sequences_truncated() {
+        let outpath = std::env::temp_dir().join("fasta_rs_truncate_sequences.fasta");
+        let written =
+            truncate_sequences(Path::new("./resources/test.fasta"), &outpath, 150).unwrap();
+        assert_eq!(written, 3);
+        let lengths = FastaReader::new(&outpath)
+            .map(Result::unwrap)
+            .map(|[_, s]| s.len())
+            .collect::<Vec<_>>();
+        assert_eq!(lengths, vec![120, 120, 150]);
+    }
+
+    #[test]
+    fn columns_split_and_joined() {
+        let tmp = std::env::temp_dir();
+        let descriptions = tmp.join("fasta_rs_columns.descriptions");
+        let sequences = tmp.join("fasta_rs_columns.sequences");
+        let joined = tmp.join("fasta_rs_columns_joined.fasta");
+        let input = Path::new("./resources/test.fasta");
+
+        assert_eq!(split_columns(input, &descriptions, &sequences).unwrap(), 3);
+        assert_eq!(fs::read_to_string(&sequences).unwrap().lines().count(), 3);
+        assert_eq!(join_columns(&descriptions, &sequences, &joined).unwrap(), 3);
+        assert_eq!(
+            FastaReader::new(&joined)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+            FastaReader::new(input)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        );
+
+        fs::write(&sequences, "ACGT\n").unwrap();
+        let err = join_columns(&descriptions, &sequences, &joined).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn length_outliers() {
+        let outliers = flag_length_outliers(Path::new("./resources/test.fasta"), 121, 200).unwrap();
+        assert_eq!(
+            outliers,
+            vec![
+                ("sp|Q2HZH0|IL1B_PUSHI".to_string(), 120),
+                ("tr|P93158|P93158_GOSHI".to_string(), 120)
+            ]
+        );
+        assert!(
+            flag_length_outliers(Path::new("./resources/test.fasta"), 120, 180)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn entries_reordered_by_index() {
+        let fasta = Path::new("./resources/test.fasta");
+        let outpath = std::env::temp_dir().join("fasta_rs_reorder.fasta");
+        let index = FastaIndex::new(fasta, "|", 1);
+        let order = ["H0VS30", "XXXXXX", "Q2HZH0"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        let (written, missing) = reorder(fasta, &index, &order, &outpath).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(missing, vec!["XXXXXX".to_string()]);
+        let ids = FastaAccessions::from_fasta(&outpath, "|", 1).accessions;
+        assert_eq!(ids, vec!["H0VS30", "Q2HZH0"]);
+    }
+
+    #[test]
+    fn fasta_as_tsv() {
+        let outpath = std::env::temp_dir().join("fasta_rs_fasta_to_tsv.tsv");
+        let count = fasta_to_tsv(Path::new("./resources/test.fasta"), &outpath, "|", 1).unwrap();
+        assert_eq!(count, 3);
+        let table = fs::read_to_string(&outpath).unwrap();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with(
+            "P93158\tTLKVPVHVPSPSEDAEWQLRKAFEGWGTNEQLIIDILAHRNAAQRNSIRKVYGEAYGEDLLKCL"
+        ));
+    }
+
+    #[test]
+    fn tsv_round_trip() {
+        let tsv = std::env::temp_dir().join("fasta_rs_round_trip.tsv");
+        let fasta = std::env::temp_dir().join("fasta_rs_round_trip.fasta");
+        let input = Path::new("./resources/test.fasta");
+        fasta_to_tsv(input, &tsv, "|", 1).unwrap();
+        assert_eq!(tsv_to_fasta(&tsv, &fasta, 60).unwrap(), 3);
+        let original = FastaReader::new(input)
+            .map(Result::unwrap)
+            .map(|[_, seq]| seq)
+            .collect::<Vec<_>>();
+        let restored = FastaReader::new(&fasta)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(restored[0][0], ">Q2HZH0");
+        assert_eq!(
+            restored.into_iter().map(|[_, seq]| seq).collect::<Vec<_>>(),
+            original
+        );
+
+        fs::write(&tsv, "a\tAC\n\nb\tGG\n").unwrap();
+        assert_eq!(tsv_to_fasta(&tsv, &fasta, 0).unwrap(), 2);
+        assert_eq!(fs::read_to_string(&fasta).unwrap(), ">a\nAC\n>b\nGG\n");
+
+        fs::write(&tsv, "a\tAC\nb GG\n").unwrap();
+        let err = tsv_to_fasta(&tsv, &fasta, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn longest_entries() {
+        let input = std::env::temp_dir().join("fasta_rs_top_n_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_top_n_out.fasta");
+        fs::write(&input, ">a\nAC\n>b\nACGT\n>c\nA\n>d\nACGTA\n>e\nTTTT\n").unwrap();
+        assert_eq!(top_n_longest(&input, &output, 3).unwrap(), 3);
+        let ids = FastaReader::new(&output)
+            .map(Result::unwrap)
+            .map(|[description, _]| description)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![">d", ">b", ">e"]);
+        assert_eq!(top_n_longest(&input, &output, 10).unwrap(), 5);
+        assert_eq!(top_n_longest(&input, &output, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn padded_file() {
+        let input = std::env::temp_dir().join("fasta_rs_pad_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_pad_out.fasta");
+        fs::write(&input, ">a\nAC\n>b\nACGTAC\n").unwrap();
+        pad_sequences(&input, &output, 4, 'N', PadAlign::Right, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a\nNNAC\n>b\nACGTAC\n"
+        );
+        pad_sequences(&input, &output, 4, 'N', PadAlign::Right, true).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), ">a\nNNAC\n>b\nACGT\n");
+    }
+
+    #[test]
+    fn alignment_consensus() {
+        let path = std::env::temp_dir().join("fasta_rs_consensus.fasta");
+        fs::write(&path, ">a\nAC-T\n>b\nac-A\n>c\nGTGA\n>d\nGT-T\n").unwrap();
+        let res = consensus(&path, 0.5).unwrap();
+        assert_eq!(res.description, ">consensus");
+        assert_eq!(res.sequence, "NN-N");
+        assert_eq!(consensus(&path, 0.25).unwrap().sequence, "AC-A");
+
+        fs::write(&path, ">a\nACGT\n>b\nACG\n").unwrap();
+        assert_eq!(
+            consensus(&path, 0.5).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn gap_columns_removed() {
+        let input = std::env::temp_dir().join("fasta_rs_gap_columns_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_gap_columns_out.fasta");
+        fs::write(&input, ">a\nA-C-.T\n>b\nA-G.-T\n>c\n--G--T\n").unwrap();
+        assert_eq!(remove_all_gap_columns(&input, &output).unwrap(), 3);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a\nACT\n>b\nAGT\n>c\n-GT\n"
+        );
+
+        fs::write(&input, ">a\nA-C\n>b\nA-\n").unwrap();
+        assert_eq!(
+            remove_all_gap_columns(&input, &output).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn unique_ids() {
+        let input = std::env::temp_dir().join("fasta_rs_uniquify_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_uniquify_out.fasta");
+        fs::write(
+            &input,
+            ">a x\nA\n>a_2\nC\n>a y\nG\n>b\nT\n>a z\nA\n>a_2\nC\n",
+        )
+        .unwrap();
+        assert_eq!(uniquify_ids(&input, &output, " ", 0).unwrap(), 3);
+        let descriptions = FastaReader::new(&output)
+            .map(Result::unwrap)
+            .map(|[description, _]| description)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptions,
+            vec![">a x", ">a_2", ">a_3 y", ">b", ">a_4 z", ">a_2_2"]
+        );
+        assert!(id_duplicates(&output, " ", 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn metrics_table() {
+        let input = std::env::temp_dir().join("fasta_rs_metrics.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_metrics.tsv");
+        fs::write(&input, ">a|x\nACGTNNgc\n>b|y\nnnnn\n").unwrap();
+        assert_eq!(metrics_report(&input, "|", 0, &output).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "id\tlength\tgc_content\tn_count\tlowercase_fraction\n\
+             a\t8\t0.6667\t2\t0.2500\n\
+             b\t4\t0.0000\t4\t1.0000\n"
+        );
+    }
+
+    #[test]
+    fn validation_of_line_widths() {
+        let path = std::env::temp_dir().join("fasta_rs_validate.fasta");
+        fs::write(
+            &path,
+            ">ok one\nACGT\nACGT\nAC\n\n\
+             >ragged\r\nACGT\r\nACG\r\nACGT\r\n\
+             >long_last\nACG\nACGT\n\
+             >gap\nACGT\n\nACGT\n\
+             >single\nACGTACGT\n",
+        )
+        .unwrap();
+        let report = validate(&path).unwrap();
+        assert_eq!(report.entries, 5);
+        assert_eq!(
+            report.inconsistent_line_widths,
+            vec![
+                ("ragged".to_string(), vec![4, 3]),
+                ("long_last".to_string(), vec![3, 4]),
+                ("gap".to_string(), vec![4, 0]),
+            ]
+        );
+        assert!(!report.is_valid());
+
+        let report = validate(Path::new("./resources/test_comments.fasta")).unwrap();
+        assert_eq!(report.entries, 3);
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn alphabet_of_file() {
+        let path = std::env::temp_dir().join("fasta_rs_alphabet.fasta");
+        fs::write(&path, ">a one\nACGTN\nacgt\n>b two\nACGT\nARGT\n").unwrap();
+        assert_eq!(
+            validate_alphabet(&path, Alphabet::DnaAmbiguous).unwrap(),
+            Ok(())
+        );
+        assert_eq!(
+            validate_alphabet(&path, Alphabet::Dna).unwrap(),
+            Err(("b".to_string(), 'R', 5))
+        );
+        assert_eq!(
+            validate_alphabet(&path, Alphabet::Rna).unwrap(),
+            Err(("a".to_string(), 'T', 3))
+        );
+    }
+
+    #[test]
+    fn duplicated_ids() {
+        let path = std::env::temp_dir().join("fasta_rs_duplicated_ids.fasta");
+        fs::write(&path, ">a\nA\n>b\nB\n>a x\nA\n>c\nC\n>a\nA\n>c\nC\n").unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("a".to_string(), 3);
+        expected.insert("c".to_string(), 2);
+        assert_eq!(id_duplicates(&path, " ", 0).unwrap(), expected);
+        assert!(id_duplicates(Path::new("./resources/test.fasta"), "|", 1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn sorted_by_id() {
+        let path = std::env::temp_dir().join("fasta_rs_sorted_by_id.fasta");
+        fs::write(&path, ">a\nA\n>b 2\nB\n>b 1\nB\n>c\nC\n").unwrap();
+        assert!(is_sorted_by_id(&path, " ", 0).unwrap());
+        assert!(!is_sorted_by_id(Path::new("./resources/test.fasta"), "|", 1).unwrap());
+        assert!(is_sorted_by_id(Path::new("./resources/test_reordered.fasta"), "|", 1).unwrap());
+
+        // sequences are not decoded
+        fs::write(&path, b">a\nAC\xff\n>b\nGG\n").unwrap();
+        assert!(is_sorted_by_id(&path, " ", 0).unwrap());
+    }
+
+    #[test]
+    fn merged_sorted_files() {
+        let a = std::env::temp_dir().join("fasta_rs_merge_a.fasta");
+        let b = std::env::temp_dir().join("fasta_rs_merge_b.fasta");
+        fs::write(&a, ">a\nAA\n>c\nCC\n>d\nDD\n").unwrap();
+        fs::write(&b, ">b\nBB\n>c\nGG\n").unwrap();
+        let items = merge_sorted(&a, &b, " ", 0)
+            .unwrap()
+            .map(|item| match item.unwrap() {
+                MergeItem::OnlyA(e) => format!("A:{}", e.sequence),
+                MergeItem::OnlyB(e) => format!("B:{}", e.sequence),
+                MergeItem::Both(e, f) => format!("AB:{}{}", e.sequence, f.sequence),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec!["A:AA", "B:BB", "AB:CCGG", "A:DD"]);
+
+        fs::write(&b, ">c\nGG\n>b\nBB\n").unwrap();
+        let res = merge_sorted(&a, &b, " ", 0).unwrap().collect::<Vec<_>>();
+        match res.last().unwrap() {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(*kind, ErrorKind::NotSorted),
+            other => panic!("expected NotSorted error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sequence_replaced_in_copy() {
+        let fasta = std::env::temp_dir().join("fasta_rs_replace_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_replace_out.fasta");
+        fs::write(
+            &fasta,
+            ">a first\nACGT\nAC\n>b second\nGG\n\n>c\nTTTT\nTT\n",
+        )
+        .unwrap();
+        let index = FastaIndex::new(&fasta, " ", 0);
+        replace_sequence(&fasta, &index, "b", "CCCCCCCCC", &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a first\nACGT\nAC\n>b second\nCCCC\nCCCC\nC\n\n>c\nTTTT\nTT\n"
+        );
+        replace_sequence(&fasta, &index, "c", "A", &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a first\nACGT\nAC\n>b second\nGG\n\n>c\nA\n"
+        );
+        match replace_sequence(&fasta, &index, "d", "A", &output) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::IdNotFound),
+            other => panic!("expected IdNotFound error, got {:?}", other),
+        }
+
+        fs::write(&fasta, ">a x\r\nACGTACGT\r\n>b y\r\nGG\r\n").unwrap();
+        let index = FastaIndex::new(&fasta, " ", 0);
+        replace_sequence(&fasta, &index, "a", "TTTTTTTTTTTT", &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a x\r\nTTTTTTTTTTTT\r\n>b y\r\nGG\r\n"
+        );
+
+        // comments of the replaced entry are kept
+        fs::write(&fasta, ">a x\n;first\nACG\n;second\nTA\n>b y\nGG\n;last").unwrap();
+        let index = FastaIndex::new(&fasta, " ", 0);
+        replace_sequence(&fasta, &index, "a", "TTTTTTT", &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a x\n;first\n;second\nTTT\nTTT\nT\n>b y\nGG\n;last"
+        );
+        replace_sequence(&fasta, &index, "b", "CC", &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a x\n;first\nACG\n;second\nTA\n>b y\n;last\nCC\n"
+        );
+    }
+
+    #[test]
+    fn canonicalized_copy() {
+        let input = std::env::temp_dir().join("fasta_rs_canonicalize_in.fasta");
+        let output = std::env::temp_dir().join("fasta_rs_canonicalize_out.fasta");
+        fs::write(&input, ">b second  \nac gt\nAC*\n>a first\nmkv**\n").unwrap();
+        let opts = CanonicalizeOptions {
+            width: 4,
+            ..Default::default()
+        };
+        assert_eq!(canonicalize(&input, &output, opts).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">a first\nMKV\n>b second\nACGT\nAC\n"
+        );
+
+        let opts = CanonicalizeOptions {
+            strip_whitespace: false,
+            uppercase: false,
+            strip_stops: false,
+            sort_by_id: false,
+            width: 0,
+        };
+        canonicalize(&input, &output, opts).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            ">b second  \nac gtAC*\n>a first\nmkv**\n"
+        );
+    }
+
+    #[test]
+    fn diff_of_versions() {
+        let new = std::env::temp_dir().join("fasta_rs_diff_new.fasta");
+        fs::write(
+            &new,
+            ">sp|Q2HZH0|IL1B_PUSHI\n\
+             MATVPEPTSEMMSYYYSDNENDLFFEADGPRKMKCCFQDLNNSSLKDEGIQLHISHQLQN\n\
+             KSLRHFVSVVVALEKLKKISLPCSQPLQDDDLKNVFCCIFEEEPIVCEVYDDDAFVCDAP\n\
+             >tr|P93158|P93158_GOSHI\nTLKVPVHV\n\
+             >sp|P01584|IL1B_HUMAN\nMAEVPELASEMMAYYSG\n",
+        )
+        .unwrap();
+        let res = diff(Path::new("./resources/test.fasta"), &new, "|", 1).unwrap();
+        assert_eq!(
+            res,
+            FastaDiff {
+                added: vec!["P01584".to_string()],
+                removed: vec!["H0VS30".to_string()],
+                changed: vec!["P93158".to_string()],
+            }
+        );
+
+        // duplicated old ids are removed once, case changes are not reported
+        let old = std::env::temp_dir().join("fasta_rs_diff_old.fasta");
+        fs::write(&old, ">a\nACGT\n>b\nGG\n>b\nTT\n").unwrap();
+        fs::write(&new, ">a\nacgt\n").unwrap();
+        let res = diff(&old, &new, " ", 0).unwrap();
+        assert_eq!(res.removed, vec!["b".to_string()]);
+        assert!(res.changed.is_empty());
+    }
+
+    #[test]
+    fn entries_from_gz() {
+        let entries = iter_gz(Path::new("./resources/test.fasta.gz"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let expected = FastaReader::new(Path::new("./resources/test.fasta"))
+            .map(Result::unwrap)
+            .map(FastaEntry::from)
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+        assert!(iter_gz(Path::new("./resources/test.fasta")).is_err());
+    }
+
+    // The number of open file descriptors of this process that refer to `path`.
+    #[cfg(target_os = "linux")]
+    fn open_handles(path: &Path) -> usize {
+        let path = fs::canonicalize(path).unwrap();
+        fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .filter_map(|fd| fs::read_link(fd.ok()?.path()).ok())
+            .filter(|target| *target == path)
+            .count()
+    }
+
+    #[test]
+    fn dropped_readers_release_files() {
+        let plain = std::env::temp_dir().join("fasta_rs_reader_handles.fasta");
+        let gz = std::env::temp_dir().join("fasta_rs_reader_handles.fasta.gz");
+        fs::copy("./resources/test.fasta", &plain).unwrap();
+        fs::copy("./resources/test.fasta.gz", &gz).unwrap();
+        for _ in 0..300 {
+            let mut entries = iter_gz(&gz).unwrap();
+            assert!(entries.next().unwrap().is_ok());
+            let mut reader = FastaReader::new(&plain);
+            assert!(reader.next().unwrap().is_ok());
+        }
+
+        // exhausted readers close their input before they are dropped
+        let mut entries = iter_gz(&gz).unwrap();
+        let mut reader = FastaReader::new(&plain);
+        assert_eq!(entries.by_ref().count(), 3);
+        assert_eq!(reader.by_ref().count(), 3);
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(open_handles(&gz), 0);
+            assert_eq!(open_handles(&plain), 0);
+            drop((entries, reader));
+            let mut open = iter_gz(&gz).unwrap();
+            assert!(open.next().unwrap().is_ok());
+            assert_eq!(open_handles(&gz), 1);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn entries_from_zstd() {
+        let path = Path::new("./resources/test.fasta.zst");
+        let expected = read_all(Path::new("./resources/test.fasta")).unwrap();
+        assert_eq!(read_all(path).unwrap(), expected);
+        assert_eq!(last_entry(path).unwrap().as_ref(), expected.last());
+        assert_eq!(estimate_record_count(path).unwrap(), 3);
+        assert!(matches!(
+            FastaHandle::open_fasta(path),
+            FastaHandle::Zstd(_)
+        ));
+        let index = FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1);
+        assert!(crate::indexed::IndexedReader::new(path, index).is_err());
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn zstd_needs_feature() {
+        match FastaReader::try_new(Path::new("./resources/test.fasta.zst")) {
+            Err(e) => assert!(e.to_string().contains("`zstd` feature")),
+            Ok(_) => panic!("expected an error without the zstd feature"),
+        }
+    }
+
+    #[test]
+    fn gc_over_all_entries() {
+        let path = std::env::temp_dir().join("fasta_rs_total_gc.fasta");
+        fs::write(&path, ">a\nGGGG\n>b\nATNN\n>c\n\n").unwrap();
+        // 4 of 6 unambiguous bases, not the mean of 1.0 and 0.0
+        assert_eq!(total_gc_content(&path).unwrap(), 4.0 / 6.0);
+        fs::write(&path, ">a\nNNN\n").unwrap();
+        assert_eq!(total_gc_content(&path).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn comment_lines() {
+        let path = Path::new("./resources/test_comments.fasta");
+        let expected = read_all(Path::new("./resources/test.fasta")).unwrap();
+        assert_eq!(read_all(path).unwrap(), expected);
+
+        let records = FastaReader::new(path)
+            .with_comments()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let comments: Vec<_> = records.iter().map(|(c, _)| c.len()).collect();
+        assert_eq!(comments, vec![2, 1, 1]);
+        assert_eq!(records[2].0, vec![";end of file".to_string()]);
+        assert_eq!(records[1].1, expected[1]);
+
+        let mut raw = Vec::new();
+        for record in FastaReader::new(path).with_raw() {
+            raw.extend(record.unwrap().0);
+        }
+        assert_eq!(raw, fs::read(path).unwrap());
+
+        let index = FastaIndex::new(path, "|", 1);
+        let mut reader = crate::indexed::IndexedReader::new(path, index).unwrap();
+        assert_eq!(reader.get("P93158").unwrap().unwrap(), expected[1]);
+    }
+
+    #[test]
+    fn entries_from_reader() {
+        let expected = FastaReader::new(Path::new("./resources/test.fasta"))
+            .entries()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for path in ["./resources/test.fasta", "./resources/test.fasta.gz"] {
+            let bytes = std::io::Cursor::new(fs::read(path).unwrap());
+            let entries = FastaReader::from_reader(bytes)
+                .unwrap()
+                .entries()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(entries, expected);
+        }
+        let empty: &[u8] = b"";
+        assert_eq!(FastaReader::from_reader(empty).unwrap().count(), 0);
+        let headerless: &[u8] = b"ACGT\n>a\nGG\n";
+        match FastaReader::from_reader(headerless) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::NoDescription),
+            Err(e) => panic!("expected NoDescription error, got {:?}", e),
+            Ok(_) => panic!("expected NoDescription error"),
+        }
+    }
+
+    // A reader that returns at most one byte per call, like a slow pipe.
+    struct OneByteReader(std::io::Cursor<Vec<u8>>);
+
+    impl Read for OneByteReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn compression_detected_from_short_reads() {
+        let expected = read_all(Path::new("./resources/test.fasta")).unwrap();
+        let mut paths = vec!["./resources/test.fasta", "./resources/test.fasta.gz"];
+        if cfg!(feature = "zstd") {
+            paths.push("./resources/test.fasta.zst");
+        }
+        for path in paths {
+            let reader = OneByteReader(std::io::Cursor::new(fs::read(path).unwrap()));
+            let entries = FastaReader::from_reader(reader)
+                .unwrap()
+                .map(|record| record.map(FastaEntry::from))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(entries, expected, "{}", path);
+        }
+        let short = OneByteReader(std::io::Cursor::new(b">a".to_vec()));
+        assert_eq!(FastaReader::from_reader(short).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn entries_by_name() {
+        let path = Path::new("./resources/test.fasta");
+        let entries = FastaReader::new(path)
+            .entries()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let all = read_all(path).unwrap();
+        assert_eq!(entries.len(), all.len());
+        for (entry, expected) in entries.iter().zip(&all) {
+            assert!(!entry.description.starts_with('>'));
+            assert_eq!(entry.description, expected.description[1..]);
+            assert_eq!(entry.sequence, expected.sequence);
+        }
+    }
+
+    #[test]
+    fn gzip_detected_by_content() {
+        let expected = read_all(Path::new("./resources/test.fasta")).unwrap();
+        let misnamed = [
+            Path::new("./resources/test_gzip_misnamed.fasta"),
+            Path::new("./resources/test_plain_misnamed.fasta.gz"),
+        ];
+        for path in misnamed {
+            assert_eq!(read_all(path).unwrap(), expected);
+            assert_eq!(last_entry(path).unwrap().as_ref(), expected.last());
+            assert_eq!(estimate_record_count(path).unwrap(), 3);
+        }
+        let index = FastaIndex::new(misnamed[1], "|", 1);
+        assert_eq!(
+            index,
+            FastaIndex::new(Path::new("./resources/test.fasta"), "|", 1)
+        );
+        assert!(matches!(
+            FastaHandle::open_fasta(misnamed[0]),
+            FastaHandle::Compressed(_)
+        ));
+    }
+
+    #[test]
+    fn record_count_estimates() {
+        for path in &["./resources/test.fasta", "./resources/test.fasta.gz"] {
+            assert_eq!(estimate_record_count(Path::new(path)).unwrap(), 3);
+        }
+
+        let path = std::env::temp_dir().join("fasta_rs_estimate_record_count.fasta");
+        let mut writer = FastaWriter::new(&path).unwrap();
+        for i in 0..1000 {
+            writer
+                .write_entry(&format!("seq{:04}", i), &"ACGT".repeat(30))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(estimate_record_count_with(&path, 2000).unwrap(), 1000);
+    }
+
+    #[test]
+    fn all_entries_in_order() {
+        let path = std::env::temp_dir().join("fasta_rs_read_all.fasta");
+        fs::write(&path, ">b\nAC\n>a\nGG\n>b\nTT\n").unwrap();
+        let entries = read_all(&path).unwrap();
+        let records = entries
+            .iter()
+            .map(|e| (e.description.as_str(), e.sequence.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(records, vec![(">b", "AC"), (">a", "GG"), (">b", "TT")]);
+
+        fs::write(&path, "").unwrap();
+        assert!(read_all(&path).unwrap().is_empty());
+        fs::write(&path, "AC\n").unwrap();
+        assert_eq!(
+            read_all(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn entries_of_multiple_files() {
+        let no_newline = Path::new("./resources/test_no_newline.fasta");
+        let missing = Path::new("./resources/does_not_exist.fasta");
+        let paths = [no_newline, missing, Path::new("./resources/test.fasta.gz")];
+        let entries = multi_reader(&paths).collect::<Vec<_>>();
+        let first = FastaReader::new(no_newline).map(Result::unwrap).count();
+        assert_eq!(entries.len(), first + 1 + 3);
+        assert!(matches!(entries[first], Err(FastaError::Io(_))));
+        assert!(entries[first + 1]
+            .as_ref()
+            .unwrap()
+            .description
+            .starts_with(">sp|Q2HZH0|"));
+        let last_of_first = entries[first - 1].as_ref().unwrap();
+        assert_eq!(
+            [
+                last_of_first.description.clone(),
+                last_of_first.sequence.clone()
+            ],
+            FastaReader::new(no_newline)
+                .map(Result::unwrap)
+                .last()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn peeked_headers() {
+        let mut reader = FastaReader::new(Path::new("./resources/test_short_descr.fasta"));
+        for id in [">Q2HZH0", ">P93158", ">H0VS30"] {
+            assert_eq!(reader.peek_header(), Some(id));
+            assert_eq!(reader.peek_header(), Some(id));
+            assert_eq!(reader.next().unwrap().unwrap()[0], id);
+        }
+        assert_eq!(reader.peek_header(), None);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn raw_bytes_per_record() {
+        let path = std::env::temp_dir().join("fasta_rs_raw_records.fasta");
+        let content = "; preamble\r\n>a\r\nAC\r\nGT\r\n\r\n>b desc\nGG\n>c\nTT";
+        fs::write(&path, content).unwrap();
+        let records = FastaReader::new(&path)
+            .with_raw()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let raw = records
+            .iter()
+            .map(|(raw, _)| String::from_utf8(raw.clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            raw,
+            vec![
+                "; preamble\r\n>a\r\nAC\r\nGT\r\n\r\n",
+                ">b desc\nGG\n",
+                ">c\nTT"
+            ]
+        );
+        assert_eq!(records[0].1.sequence, "ACGT");
+        assert_eq!(records[1].1.description, ">b desc");
+    }
+
+    #[test]
+    fn lenient_reader_names_headerless_sequences() {
+        let entries = FastaReader::new_lenient(Path::new("./resources/test_headerless.fasta"))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let descriptions = entries.iter().map(|[d, _]| d.as_str()).collect::<Vec<_>>();
+        assert_eq!(descriptions, vec![">unnamed_1", ">P93158", ">unnamed_2"]);
+        assert_eq!(entries[0][1].len(), 120);
+        assert_eq!(entries[1][1].len(), 120);
+        assert_eq!(entries[2][1].len(), 180);
+    }
+
+    #[test]
+    fn entries_filtered_by_description() {
+        let outpath = std::env::temp_dir().join("fasta_rs_filter_by_description.fasta");
+        let infile = Path::new("./resources/test.fasta");
+        assert_eq!(
+            filter_by_description(infile, &outpath, "OS=Pusa", false).unwrap(),
+            1
+        );
+        assert_eq!(
+            FastaReader::new(&outpath)
+                .map(Result::unwrap)
+                .next()
+                .unwrap()[1]
+                .len(),
+            120
+        );
+        assert_eq!(
+            filter_by_description(infile, &outpath, "os=", false).unwrap(),
+            0
+        );
+        assert_eq!(
+            filter_by_description(infile, &outpath, "os=", true).unwrap(),
+            3
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn entries_filtered_by_description_regex() {
+        let outpath = std::env::temp_dir().join("fasta_rs_filter_by_description_regex.fasta");
+        let pattern = Regex::new(r"PE=[23] SV=1$").unwrap();
+        let written =
+            filter_by_description_regex(Path::new("./resources/test.fasta"), &outpath, &pattern)
+                .unwrap();
+        assert_eq!(written, 2);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn ids_renamed_by_regex() {
+        let outpath = std::env::temp_dir().join("fasta_rs_rename_by_regex.fasta");
+        let pattern = Regex::new(r"^\w+\|(\w+)\|\w+$").unwrap();
+        let renamed = rename_by_regex(
+            Path::new("./resources/test.fasta"),
+            &outpath,
+            &pattern,
+            "$1",
+        )
+        .unwrap();
+        assert_eq!(renamed, 3);
+
+        let entries = FastaReader::new(&outpath)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries[0][0],
+            ">Q2HZH0 Interleukin-1 beta OS=Pusa hispida OX=9718 GN=IL1B PE=2 SV=1"
+        );
+        assert_eq!(entries[2][1].len(), 180);
+    }
+}
@@ -0,0 +1,127 @@
+//! Composable per-entry sequence transforms.
+
+use crate::pieces::FastaEntry;
+
+/// A transformation of a single FASTA entry.
+pub trait SeqTransform {
+    fn apply(&self, entry: FastaEntry) -> FastaEntry;
+}
+
+impl<F: Fn(FastaEntry) -> FastaEntry> SeqTransform for F {
+    fn apply(&self, entry: FastaEntry) -> FastaEntry {
+        self(entry)
+    }
+}
+
+/// An ordered chain of transforms that is applied as a single one.
+///
+/// # Examples
+/// ```
+/// use fasta::pieces::FastaEntry;
+/// use fasta::transform::{SeqTransform, TransformPipeline, Ungap, Uppercase};
+///
+/// let mut pipeline = TransformPipeline::new();
+/// pipeline.push(Ungap);
+/// pipeline.push(Uppercase);
+///
+/// let entry = FastaEntry {
+///     description: ">aligned".to_string(),
+///     sequence: "ac-g.t".to_string(),
+/// };
+/// assert_eq!(pipeline.apply(entry).sequence, "ACGT");
+/// ```
+#[derive(Default)]
+pub struct TransformPipeline {
+    pub transforms: Vec<Box<dyn SeqTransform>>,
+}
+
+impl TransformPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a transform to the end of the pipeline.
+    pub fn push<T: SeqTransform + 'static>(&mut self, transform: T) {
+        self.transforms.push(Box::new(transform));
+    }
+}
+
+impl SeqTransform for TransformPipeline {
+    fn apply(&self, entry: FastaEntry) -> FastaEntry {
+        self.transforms
+            .iter()
+            .fold(entry, |entry, transform| transform.apply(entry))
+    }
+}
+
+/// Removes gap characters (`-` and `.`) from the sequence.
+pub struct Ungap;
+
+impl SeqTransform for Ungap {
+    fn apply(&self, mut entry: FastaEntry) -> FastaEntry {
+        entry.sequence.retain(|c| c != '-' && c != '.');
+        entry
+    }
+}
+
+/// Converts the sequence to upper case.
+pub struct Uppercase;
+
+impl SeqTransform for Uppercase {
+    fn apply(&self, mut entry: FastaEntry) -> FastaEntry {
+        entry.sequence.make_ascii_uppercase();
+        entry
+    }
+}
+
+/// Cuts the sequence to at most the given number of residues.
+pub struct Truncate(pub usize);
+
+impl SeqTransform for Truncate {
+    fn apply(&self, entry: FastaEntry) -> FastaEntry {
+        entry.truncate(self.0)
+    }
+}
+
+/// Replaces `U` by `T`, see `FastaEntry::to_dna`.
+pub struct ToDna;
+
+impl SeqTransform for ToDna {
+    fn apply(&self, entry: FastaEntry) -> FastaEntry {
+        entry.to_dna()
+    }
+}
+
+/// Replaces `T` by `U`, see `FastaEntry::to_rna`.
+pub struct ToRna;
+
+impl SeqTransform for ToRna {
+    fn apply(&self, entry: FastaEntry) -> FastaEntry {
+        entry.to_rna()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_applied_in_order() {
+        let mut pipeline = TransformPipeline::new();
+        pipeline.push(Ungap);
+        pipeline.push(ToRna);
+        pipeline.push(|mut entry: FastaEntry| {
+            entry.sequence.truncate(3);
+            entry
+        });
+        pipeline.push(Uppercase);
+
+        let entry = FastaEntry {
+            description: ">seq".to_string(),
+            sequence: "-t-acg".to_string(),
+        };
+        let res = pipeline.apply(entry);
+        assert_eq!(res.sequence, "UAC");
+        assert_eq!(res.description, ">seq");
+    }
+}
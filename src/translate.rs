@@ -0,0 +1,220 @@
+//! Translation of nucleotide sequences to protein sequences.
+
+use crate::errors::{ErrorKind, FastaError};
+
+/// A genetic code, numbered like the NCBI translation tables.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TranslationTable {
+    /// The standard code (table 1).
+    Standard,
+    /// The vertebrate mitochondrial code (table 2).
+    VertebrateMitochondrial,
+}
+
+impl TranslationTable {
+    /// The NCBI number of the table.
+    pub fn id(self) -> u8 {
+        match self {
+            TranslationTable::Standard => 1,
+            TranslationTable::VertebrateMitochondrial => 2,
+        }
+    }
+
+    // The amino acids of all codons, ordered by bases TCAG, as in the NCBI tables.
+    fn amino_acids(self) -> &'static [u8; 64] {
+        match self {
+            TranslationTable::Standard => {
+                b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            TranslationTable::VertebrateMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"
+            }
+        }
+    }
+}
+
+/// How to handle bases after the last complete codon.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TrailingBases {
+    /// Fail with `ErrorKind::IncompleteCodon`.
+    Error,
+    /// Ignore them.
+    Truncate,
+}
+
+/// Translates a nucleotide sequence in frame from its first base.
+///
+/// Fails if the length of the sequence is not a multiple of three,
+/// see `translate_with` to ignore trailing bases instead.
+///
+/// # Examples
+/// ```
+/// use fasta::translate::{translate, TranslationTable};
+///
+/// assert_eq!(translate("ATGgcuTGA", TranslationTable::Standard).unwrap(), "MA*");
+/// assert!(translate("ATGGC", TranslationTable::Standard).is_err());
+/// ```
+pub fn translate(seq: &str, table: TranslationTable) -> Result<String, FastaError> {
+    translate_with(seq, table, TrailingBases::Error)
+}
+
+/// Translates a nucleotide sequence in frame from its first base, handling
+/// bases after the last complete codon according to `trailing`.
+///
+/// Case is ignored and `U` is read as `T`. Stop codons are translated to `*`.
+/// Codons with IUPAC ambiguity codes are translated if all the codons they
+/// stand for code for the same amino acid, and to `X` otherwise. Other
+/// characters, including gaps, are an error.
+///
+/// # Examples
+/// ```
+/// use fasta::translate::{translate_with, TrailingBases, TranslationTable};
+///
+/// let protein = translate_with("GCNTAYRTGGG", TranslationTable::Standard, TrailingBases::Truncate);
+/// assert_eq!(protein.unwrap(), "AYX");
+/// ```
+pub fn translate_with(
+    seq: &str,
+    table: TranslationTable,
+    trailing: TrailingBases,
+) -> Result<String, FastaError> {
+    let bytes = seq.as_bytes();
+    if trailing == TrailingBases::Error && bytes.len() % 3 != 0 {
+        return Err(FastaError::parse(
+            ErrorKind::IncompleteCodon,
+            format!(
+                "Sequence length {} is not a multiple of three.",
+                bytes.len()
+            ),
+        ));
+    }
+    let amino_acids = table.amino_acids();
+    let mut res = String::with_capacity(bytes.len() / 3);
+    for (i, codon) in bytes.chunks_exact(3).enumerate() {
+        let mut options = [&[][..]; 3];
+        for (j, base) in codon.iter().enumerate() {
+            let pos = 3 * i + j;
+            options[j] = base_indices(*base).ok_or_else(|| {
+                // bases are ASCII, so this is the first byte of the first invalid character
+                let c = seq[pos..].chars().next().unwrap();
+                let position = seq[..pos].chars().count();
+                FastaError::parse(
+                    ErrorKind::InvalidCharacter,
+                    format!("Invalid nucleotide {:?} at position {}.", c, position),
+                )
+            })?;
+        }
+        // all codons the ambiguity codes stand for
+        let mut translations = options[0].iter().flat_map(|b1| {
+            options[1].iter().flat_map(move |b2| {
+                options[2]
+                    .iter()
+                    .map(move |b3| amino_acids[16 * b1 + 4 * b2 + b3])
+            })
+        });
+        let first = translations.next().unwrap();
+        if translations.all(|aa| aa == first) {
+            res.push(first as char);
+        } else {
+            res.push('X');
+        }
+    }
+    Ok(res)
+}
+
+// The indices of the bases, in order TCAG, that a nucleotide code stands for.
+fn base_indices(base: u8) -> Option<&'static [usize]> {
+    let indices: &[usize] = match base.to_ascii_uppercase() {
+        b'T' | b'U' => &[0],
+        b'C' => &[1],
+        b'A' => &[2],
+        b'G' => &[3],
+        b'R' => &[2, 3],
+        b'Y' => &[0, 1],
+        b'S' => &[1, 3],
+        b'W' => &[0, 2],
+        b'K' => &[0, 3],
+        b'M' => &[1, 2],
+        b'B' => &[0, 1, 3],
+        b'D' => &[0, 2, 3],
+        b'H' => &[0, 1, 2],
+        b'V' => &[1, 2, 3],
+        b'N' => &[0, 1, 2, 3],
+        _ => return None,
+    };
+    Some(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_gfp() {
+        // the start of the coding sequence of the green fluorescent protein
+        let cds = "ATGAGTAAAGGAGAAGAACTTTTCACTGGAGTTGTCCCAATTCTTGTTGAATTAGATGGTGATGTTAATGGG\
+                   CACAAATTTTCTGTCAGTGGAGAGGGTGAAGGTGATGCAACATACGGAAAACTTACCCTTAAATTTATTTGC\
+                   ACTACTGGAAAACTACCTGTTCCATGGCCAACACTTGTCACTACTTTC";
+        assert_eq!(
+            translate(cds, TranslationTable::Standard).unwrap(),
+            "MSKGEELFTGVVPILVELDGDVNGHKFSVSGEGEGDATYGKLTLKFICTTGKLPVPWPTLVTTF"
+        );
+        assert_eq!(
+            translate(
+                &cds.to_lowercase().replace('t', "u"),
+                TranslationTable::Standard
+            )
+            .unwrap(),
+            translate(cds, TranslationTable::Standard).unwrap()
+        );
+    }
+
+    #[test]
+    fn translation_tables() {
+        let seq = "ATAAGATGAAGG";
+        assert_eq!(translate(seq, TranslationTable::Standard).unwrap(), "IR*R");
+        assert_eq!(
+            translate(seq, TranslationTable::VertebrateMitochondrial).unwrap(),
+            "M*W*"
+        );
+        assert_eq!(TranslationTable::VertebrateMitochondrial.id(), 2);
+    }
+
+    #[test]
+    fn ambiguous_and_invalid_codons() {
+        // leucine is coded by YTR and CTN
+        assert_eq!(
+            translate("YTRCTNTAR", TranslationTable::Standard).unwrap(),
+            "LL*"
+        );
+        assert_eq!(
+            translate("NNNATH", TranslationTable::Standard).unwrap(),
+            "XI"
+        );
+        assert_eq!(
+            translate_with("ATGA", TranslationTable::Standard, TrailingBases::Truncate).unwrap(),
+            "M"
+        );
+        match translate("ATGA", TranslationTable::Standard) {
+            Err(FastaError::Parse { kind, .. }) => assert_eq!(kind, ErrorKind::IncompleteCodon),
+            other => panic!("expected IncompleteCodon error, got {:?}", other),
+        }
+        match translate("ATG-CC", TranslationTable::Standard) {
+            Err(FastaError::Parse { kind, message }) => {
+                assert_eq!(kind, ErrorKind::InvalidCharacter);
+                assert!(message.contains("'-' at position 3"));
+            }
+            other => panic!("expected InvalidCharacter error, got {:?}", other),
+        }
+        match translate_with(
+            "ATGAéXT",
+            TranslationTable::Standard,
+            TrailingBases::Truncate,
+        ) {
+            Err(FastaError::Parse { message, .. }) => {
+                assert!(message.contains("'é' at position 4"))
+            }
+            other => panic!("expected InvalidCharacter error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Utilities for writing FASTA files entry by entry.
+
+use crate::pieces::FastaEntry;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The default number of residues per sequence line.
+pub const DEFAULT_WIDTH: usize = 60;
+
+/// Formatting options for `FastaWriter`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriteOptions {
+    /// Residues per sequence line; 0 writes each sequence on a single line.
+    pub width: usize,
+    /// If set, line breaks that would fall inside a lowercase (masked) run
+    /// are moved to the nearest case transition at most this many residues
+    /// away, so that masked runs stay on one line where possible.
+    /// Lines then vary in length, which `.fai` style indexing does not support.
+    pub align_to_mask_boundaries: Option<usize>,
+    /// A line written between consecutive entries, e.g. an empty line or `//`.
+    /// Nothing is written after the last entry. Blank separator lines are
+    /// skipped by `FastaReader`, and end the sequence of the entry before them
+    /// when read through a `FastaIndex`, like in `FastaEntry::from_index`, so
+    /// the entries read back unchanged. Other separators are read as sequence.
+    pub record_separator: Option<String>,
+    /// The gzip compression level from 0 (none) to 9 (best), used for
+    /// outputs with a `.gz` extension.
+    pub compression_level: u32,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            width: DEFAULT_WIDTH,
+            align_to_mask_boundaries: None,
+            record_separator: None,
+            compression_level: 6,
+        }
+    }
+}
+
+/// A writer that streams FASTA entries to a file.
+///
+/// Sequences are wrapped to a fixed number of residues per line;
+/// a width of 0 writes each sequence on a single line.
+/// Outputs with a `.gz` extension are gzip compressed; call `finish`
+/// to make sure the compressed stream is complete.
+///
+/// # Examples
+/// ```
+/// use fasta::read::FastaReader;
+/// use fasta::write::FastaWriter;
+/// use std::path::Path;
+///
+/// let outpath = std::env::temp_dir().join("fasta_writer_doctest.fasta.gz");
+/// let mut writer = FastaWriter::with_width(&outpath, 80).unwrap();
+/// for entry in FastaReader::new(Path::new("./resources/test.fasta")).entries() {
+///     let entry = entry.unwrap();
+///     if entry.sequence.len() > 150 {
+///         writer.write_fasta_entry(&entry).unwrap();
+///     }
+/// }
+/// writer.finish().unwrap();
+/// assert_eq!(FastaReader::new(&outpath).count(), 1);
+/// ```
+pub struct FastaWriter {
+    writer: BufWriter<Output>,
+    options: WriteOptions,
+    bytes_written: u64,
+    // whether the output already holds an entry
+    has_entries: bool,
+}
+
+impl FastaWriter {
+    /// Creates a writer with the default `WriteOptions`.
+    pub fn new(path: &Path) -> io::Result<Self> {
+        Self::with_options(path, WriteOptions::default())
+    }
+
+    /// Creates a writer that wraps sequences at `width` residues.
+    pub fn with_width(path: &Path, width: usize) -> io::Result<Self> {
+        Self::with_options(
+            path,
+            WriteOptions {
+                width,
+                ..WriteOptions::default()
+            },
+        )
+    }
+
+    pub fn with_options(path: &Path, options: WriteOptions) -> io::Result<Self> {
+        Ok(Self::from_file(path, File::create(path)?, options))
+    }
+
+    /// Creates a writer that appends to an existing file.
+    ///
+    /// If the file does not end with a newline, one is written first.
+    /// For `.gz` files, the entries are appended as a new gzip member, which
+    /// gzip readers decompress as the continuation of the file; the file is
+    /// assumed to end with a newline.
+    pub fn append(path: &Path, options: WriteOptions) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+        let mut last = [b'\n'];
+        let file_len = file.seek(SeekFrom::End(0))?;
+        if file_len > 0 && !is_gz(path) {
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+        }
+        let mut res = Self::from_file(path, file, options);
+        res.has_entries = file_len > 0;
+        if last[0] != b'\n' {
+            res.write_bytes(b"\n")?;
+        }
+        Ok(res)
+    }
+
+    // A writer that keeps its output in memory, see `into_bytes`.
+    pub(crate) fn in_memory(options: WriteOptions) -> Self {
+        FastaWriter {
+            writer: BufWriter::new(Output::Memory(Vec::new())),
+            options,
+            bytes_written: 0,
+            has_entries: false,
+        }
+    }
+
+    fn from_file(path: &Path, file: File, options: WriteOptions) -> Self {
+        let output = if is_gz(path) {
+            Output::Gz(GzEncoder::new(
+                file,
+                Compression::new(options.compression_level.min(9)),
+            ))
+        } else {
+            Output::Plain(file)
+        };
+        FastaWriter {
+            writer: BufWriter::new(output),
+            options,
+            bytes_written: 0,
+            has_entries: false,
+        }
+    }
+
+    // Sets the line width of the entries written next.
+    pub(crate) fn set_width(&mut self, width: usize) {
+        self.options.width = width;
+    }
+
+    /// Writes an entry, e.g. from `FastaReader::entries`, like `write_entry`.
+    pub fn write_fasta_entry(&mut self, entry: &FastaEntry) -> io::Result<()> {
+        self.write_entry(&entry.description, &entry.sequence)
+    }
+
+    /// Writes a single entry. The `>` prefix of the description is optional.
+    pub fn write_entry(&mut self, description: &str, sequence: &str) -> io::Result<()> {
+        let description = description.strip_prefix('>').unwrap_or(description);
+        if let (true, Some(separator)) = (self.has_entries, &self.options.record_separator) {
+            let line = format!("{}\n", separator);
+            self.write_bytes(line.as_bytes())?;
+        }
+        self.has_entries = true;
+        self.write_bytes(b">")?;
+        self.write_bytes(description.as_bytes())?;
+        self.write_bytes(b"\n")?;
+
+        let seq = sequence.as_bytes();
+        let mut line_start = 0;
+        while line_start < seq.len() {
+            let line_end = self.line_end(seq, line_start);
+            self.write_bytes(&seq[line_start..line_end])?;
+            self.write_bytes(b"\n")?;
+            line_start = line_end;
+        }
+        Ok(())
+    }
+
+    // Finds the end of the sequence line starting at `start`.
+    fn line_end(&self, seq: &[u8], start: usize) -> usize {
+        let end = match self.options.width {
+            0 => return seq.len(),
+            w => start + w,
+        };
+        if end >= seq.len() {
+            return seq.len();
+        }
+        let masked = |i: usize| seq[i].is_ascii_lowercase();
+        match self.options.align_to_mask_boundaries {
+            Some(tolerance) if masked(end - 1) && masked(end) => {
+                let is_boundary =
+                    |i: usize| i > start && i < seq.len() && masked(i - 1) != masked(i);
+                // closest case transition, preferring shorter lines
+                for d in 1..=tolerance {
+                    if d < end && is_boundary(end - d) {
+                        return end - d;
+                    } else if is_boundary(end + d) {
+                        return end + d;
+                    }
+                }
+                end
+            }
+            _ => end,
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.bytes_written += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// The number of bytes written so far, before compression.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Flushes all buffered output to the file and completes the gzip stream.
+    pub fn finish(self) -> io::Result<()> {
+        match self.writer.into_inner().map_err(|e| e.into_error())? {
+            Output::Plain(mut file) => file.flush(),
+            Output::Gz(encoder) => encoder.finish().map(|_| ()),
+            Output::Memory(_) => Ok(()),
+        }
+    }
+
+    // The output of a writer created with `in_memory`.
+    pub(crate) fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self.writer.into_inner().map_err(|e| e.into_error())? {
+            Output::Memory(bytes) => Ok(bytes),
+            _ => Err(io::Error::other("writer does not write to memory")),
+        }
+    }
+}
+
+// The file a `FastaWriter` writes to.
+enum Output {
+    Plain(File),
+    Gz(GzEncoder<File>),
+    Memory(Vec<u8>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(file) => file.write(buf),
+            Output::Gz(encoder) => encoder.write(buf),
+            Output::Memory(bytes) => bytes.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(file) => file.flush(),
+            Output::Gz(encoder) => encoder.flush(),
+            Output::Memory(_) => Ok(()),
+        }
+    }
+}
+
+fn is_gz(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new("gz"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::FastaIndex;
+    use crate::indexed::IndexedReader;
+    use crate::map::FastaMap;
+    use crate::read::{filter_by_description, FastaReader};
+    use std::fs::read_to_string;
+
+    #[test]
+    fn wrapped_entries_round_trip() {
+        let outpath = std::env::temp_dir().join("fasta_rs_write_wrapped.fasta");
+        let mut writer = FastaWriter::with_width(&outpath, 50).unwrap();
+        let entries = FastaReader::new(Path::new("./resources/test.fasta"))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        for [description, seq] in &entries {
+            writer.write_entry(description, seq).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let written = read_to_string(&outpath).unwrap();
+        assert!(written.lines().all(|l| l.starts_with('>') || l.len() <= 50));
+        assert_eq!(
+            FastaReader::new(&outpath)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+            entries
+        );
+    }
+
+    #[test]
+    fn line_breaks_aligned_to_masking() {
+        let outpath = std::env::temp_dir().join("fasta_rs_write_mask_aligned.fasta");
+        let options = WriteOptions {
+            width: 10,
+            align_to_mask_boundaries: Some(3),
+            ..WriteOptions::default()
+        };
+        let mut writer = FastaWriter::with_options(&outpath, options).unwrap();
+        // the first default break splits a masked run
+        writer
+            .write_entry("masked", "ACGTACGacgtaACGTACGTACGTAcgtacgtACG")
+            .unwrap();
+        // no transition within tolerance: break at the width
+        writer
+            .write_entry("unaligned", "ACacgtacgtacgtacAC")
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            read_to_string(&outpath).unwrap(),
+            ">masked\nACGTACGacgta\nACGTACGTAC\nGTAcgtacgt\nACG\n\
+             >unaligned\nACacgtacgt\nacgtacAC\n"
+        );
+    }
+
+    #[test]
+    fn separators_between_records() {
+        let outpath = std::env::temp_dir().join("fasta_rs_write_separators.fasta");
+        let options = WriteOptions {
+            record_separator: Some("//".to_string()),
+            ..WriteOptions::default()
+        };
+        let mut writer = FastaWriter::with_options(&outpath, options.clone()).unwrap();
+        writer.write_entry("a", "ACGT").unwrap();
+        writer.write_entry("b", "GG").unwrap();
+        writer.finish().unwrap();
+        let mut writer = FastaWriter::append(&outpath, options).unwrap();
+        writer.write_entry("c", "TT").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            read_to_string(&outpath).unwrap(),
+            ">a\nACGT\n//\n>b\nGG\n//\n>c\nTT\n"
+        );
+
+        let options = WriteOptions {
+            record_separator: Some(String::new()),
+            ..WriteOptions::default()
+        };
+        let mut writer = FastaWriter::with_options(&outpath, options).unwrap();
+        writer.write_entry("a", "ACGT").unwrap();
+        writer.write_entry("b", "GG").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(read_to_string(&outpath).unwrap(), ">a\nACGT\n\n>b\nGG\n");
+
+        // blank separators end entries, without cutting them off, in indexed reads
+        let index = FastaIndex::new(&outpath, " ", 0);
+        let map = FastaMap::from_index_with_ids(&outpath, &index, &["a".to_string()]);
+        assert_eq!(map.id_to_seq["a"], "ACGT");
+        let mut reader = IndexedReader::new(&outpath, FastaIndex::new(&outpath, " ", 0)).unwrap();
+        for (id, seq) in [("a", "ACGT"), ("b", "GG")] {
+            let entry = FastaEntry::from_index(&outpath, index.id_to_offset[id]).unwrap();
+            assert_eq!(entry.sequence, seq);
+            assert_eq!(reader.get(id).unwrap().unwrap(), entry);
+        }
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let input = Path::new("./resources/test.fasta.gz");
+        let outpath = std::env::temp_dir().join("fasta_rs_write_filtered.fasta.gz");
+        let written = filter_by_description(input, &outpath, "IL1B", false).unwrap();
+        assert_eq!(written, 1);
+        let mut gz_magic = [0; 2];
+        File::open(&outpath)
+            .unwrap()
+            .read_exact(&mut gz_magic)
+            .unwrap();
+        assert_eq!(gz_magic, [0x1f, 0x8b]);
+
+        let expected = FastaReader::new(input)
+            .map(Result::unwrap)
+            .filter(|[description, _]| description.contains("IL1B"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            FastaReader::new(&outpath)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        // appended entries become a second gzip member
+        let options = WriteOptions {
+            compression_level: 9,
+            ..WriteOptions::default()
+        };
+        let mut writer = FastaWriter::append(&outpath, options).unwrap();
+        writer.write_entry("appended", "ACGT").unwrap();
+        writer.finish().unwrap();
+        let entries = FastaReader::new(&outpath)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1], [">appended".to_string(), "ACGT".to_string()]);
+    }
+}